@@ -0,0 +1,204 @@
+//! 複数の版の境界データを比べ、自治体の新設・廃止や形状の変化を調べる
+
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{
+    error::Result,
+    municipalities::{
+        data::PREFECTURES,
+        dataset::{Dataset, Vintage},
+    },
+    shapes::{prefecture_municipality_shapes, ShapeOptions},
+    similarity::{FourierDescriptor, DESCRIPTOR_HARMONICS},
+};
+
+/// ある版における一つの自治体の行政区域コード・名前・記述子
+#[derive(Debug, Clone)]
+pub struct CodedDescriptor {
+    pub code: String,
+    pub name: String,
+    pub descriptor: FourierDescriptor,
+}
+
+/// 版の間で行政区域コードが現れた、あるいは消えた自治体
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CodeEntry {
+    pub code: String,
+    pub name: String,
+}
+
+/// 版の間で形状が閾値を超えて変わった自治体
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ShapeChange {
+    pub code: String,
+    pub old_name: String,
+    pub new_name: String,
+    /// 記述子の距離
+    pub distance: f64,
+}
+
+/// 二つの版の間の変化
+#[derive(Serialize, Debug, Clone)]
+pub struct ChangeReport {
+    pub from: String,
+    pub to: String,
+    pub threshold: f64,
+    /// 新しい版にのみある行政区域コード（合併による新設など）
+    pub appeared: Vec<CodeEntry>,
+    /// 古い版にのみある行政区域コード（合併による廃止など）
+    pub disappeared: Vec<CodeEntry>,
+    /// 両方の版にあり、記述子の距離が閾値を超えたもの
+    pub changed: Vec<ShapeChange>,
+}
+
+impl ChangeReport {
+    /// 記述子の列どうしを行政区域コードで突き合わせて比べる。結果はコードの順に並ぶ。
+    pub fn compare(
+        from: &Vintage,
+        to: &Vintage,
+        old: &[CodedDescriptor],
+        new: &[CodedDescriptor],
+        threshold: f64,
+    ) -> Self {
+        let old_by_code = old.iter().map(|d| (&d.code, d)).collect::<HashMap<_, _>>();
+        let new_by_code = new.iter().map(|d| (&d.code, d)).collect::<HashMap<_, _>>();
+        let entry = |d: &CodedDescriptor| CodeEntry {
+            code: d.code.clone(),
+            name: d.name.clone(),
+        };
+
+        let mut appeared = new
+            .iter()
+            .filter(|d| !old_by_code.contains_key(&d.code))
+            .map(entry)
+            .collect::<Vec<_>>();
+        let mut disappeared = old
+            .iter()
+            .filter(|d| !new_by_code.contains_key(&d.code))
+            .map(entry)
+            .collect::<Vec<_>>();
+        let mut changed = old
+            .iter()
+            .filter_map(|o| {
+                let n = new_by_code.get(&o.code)?;
+                let distance = o.descriptor.distance(&n.descriptor);
+                (distance > threshold).then(|| ShapeChange {
+                    code: o.code.clone(),
+                    old_name: o.name.clone(),
+                    new_name: n.name.clone(),
+                    distance,
+                })
+            })
+            .collect::<Vec<_>>();
+        appeared.sort_by(|a, b| a.code.cmp(&b.code));
+        disappeared.sort_by(|a, b| a.code.cmp(&b.code));
+        changed.sort_by(|a, b| a.code.cmp(&b.code));
+
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            threshold,
+            appeared,
+            disappeared,
+            changed,
+        }
+    }
+}
+
+/// `dataset`の全都道府県の自治体について、行政区域コードを持つものの記述子を計算する。
+pub fn coded_descriptors(
+    dataset: &Dataset,
+    options: &ShapeOptions,
+) -> Result<Vec<CodedDescriptor>> {
+    let mut descriptors = vec![];
+    for prefecture_name in PREFECTURES.iter().skip(1) {
+        let shapes = prefecture_municipality_shapes(dataset, prefecture_name, options)?;
+        descriptors.extend(shapes.into_iter().filter_map(|shape| {
+            Some(CodedDescriptor {
+                descriptor: FourierDescriptor::new(&shape.outline(), DESCRIPTOR_HARMONICS),
+                code: shape.code?,
+                name: shape.name,
+            })
+        }));
+    }
+    Ok(descriptors)
+}
+
+/// データディレクトリ内の全ての版を古い順に並べ、隣り合う版どうしの変化を返す。
+/// 各版のファイルは一度だけ読み込む。
+pub fn track_changes(
+    root: &std::path::Path,
+    options: &ShapeOptions,
+    threshold: f64,
+) -> Result<Vec<ChangeReport>> {
+    let mut reports = vec![];
+    let mut previous: Option<(Vintage, Vec<CodedDescriptor>)> = None;
+    for vintage in Dataset::vintages(root)? {
+        let descriptors = coded_descriptors(&Dataset::new(root, vintage.clone()), options)?;
+        if let Some((old_vintage, old)) = &previous {
+            reports.push(ChangeReport::compare(
+                old_vintage,
+                &vintage,
+                old,
+                &descriptors,
+                threshold,
+            ));
+        }
+        previous = Some((vintage, descriptors));
+    }
+    Ok(reports)
+}
+
+/// 行政区域コードの新設・廃止と、閾値を超えた形状の変化が報告されることを確かめる。
+#[test]
+fn test_change_report() {
+    use rustfft::num_complex::Complex;
+
+    use crate::shapes::{flower, rectangle, simple_circle, ShapePoints};
+
+    let coded = |code: &str, name: &str, shape: ShapePoints| CodedDescriptor {
+        code: code.to_string(),
+        name: name.to_string(),
+        descriptor: FourierDescriptor::new(&shape, DESCRIPTOR_HARMONICS),
+    };
+    let moved_flower = flower()
+        .iter()
+        .map(|c| c * Complex::cis(1.0) + 30.0)
+        .collect();
+    // 28001と28002が合併して28003になり、28004の形が変わり、28005は移動しただけ
+    let old = vec![
+        coded("28001", "甲町", simple_circle()),
+        coded("28002", "乙町", rectangle()),
+        coded("28004", "丙村", simple_circle()),
+        coded("28005", "丁町", flower()),
+    ];
+    let new = vec![
+        coded("28003", "甲乙市", rectangle()),
+        coded("28004", "丙市", flower()),
+        coded("28005", "丁町", moved_flower),
+    ];
+    let from = "22_220101".parse().unwrap();
+    let to = "23_230101".parse().unwrap();
+    let report = ChangeReport::compare(&from, &to, &old, &new, 1e-6);
+
+    assert_eq!(report.from, "22_220101");
+    assert_eq!(
+        report.appeared,
+        vec![CodeEntry {
+            code: "28003".to_string(),
+            name: "甲乙市".to_string()
+        }]
+    );
+    let disappeared = report
+        .disappeared
+        .iter()
+        .map(|e| e.code.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(disappeared, ["28001", "28002"]);
+    assert_eq!(report.changed.len(), 1);
+    assert_eq!(report.changed[0].code, "28004");
+    assert_eq!(report.changed[0].old_name, "丙村");
+    assert_eq!(report.changed[0].new_name, "丙市");
+}
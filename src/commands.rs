@@ -0,0 +1,519 @@
+//! コマンドライン引数で指定された処理を実行する
+
+use std::{collections::HashMap, str::FromStr};
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::{
+    changes::track_changes,
+    error::Error,
+    index::{DescriptorIndex, IndexParams},
+    io::{output_distance_matrix_csv, output_distance_matrix_json},
+    io::{read_feature_collection, read_raw_municipalities_boundary_data},
+    municipalities::{
+        dataset::Dataset,
+        gazetteer::Gazetteer,
+        mapping::PropertyMapping,
+        name_search::search_names,
+        readings::ReadingTable,
+        utils::{geo_feature_props_to_name, is_muni_code},
+        validation::{normalize_feature, RingFix},
+    },
+    search::{
+        all_prefecture_descriptors, prefecture_miniatures, search_in_descriptors,
+        search_similar_municipalities,
+    },
+    shapes::{
+        mapped_feature_shapes, municipality_shape_with_options, prefecture_municipality_shapes,
+        ShapeOptions,
+    },
+    similarity::{DistanceMatrix, FourierDescriptor, DESCRIPTOR_HARMONICS},
+    simplify::Simplification,
+    spectrum::{reconstruction_errors, HarmonicSelection},
+};
+
+/// 境界形状を取得する際の点の数のデフォルト値
+const DEFAULT_SAMPLE_NUM: usize = 256;
+/// 類似自治体検索で返す件数のデフォルト値
+const DEFAULT_SEARCH_NUM: usize = 10;
+/// 再構成の誤差を調べる係数の個数の上限のデフォルト値
+const DEFAULT_MAX_HARMONICS: usize = 64;
+/// 再構成した曲線の点の数のデフォルト値
+const DEFAULT_RECONSTRUCTION_RESOLUTION: usize = 1024;
+/// 版の間で形状が変わったとみなす記述子の距離のデフォルト値
+const DEFAULT_CHANGE_THRESHOLD: f64 = 0.05;
+
+const USAGE: &str = "usage:
+    (引数なし)                                   可視化ウィンドウを開く
+    matrix <都道府県名> [出力ファイル名] [点数]  都道府県内の自治体の距離行列を出力する
+    geojson <GeoJSON・TopoJSON・.shpファイル> <出力ファイル名> [点数]
+                                                 任意のGeoJSONやTopoJSON、Shapefileの全Featureの距離行列を出力する
+    search <自治体名> [件数] [点数]              全国から形の似ている自治体を探す
+    index <出力ファイル名> [点数]                全国の自治体の記述子を計算して保存する
+    search-index <インデックス> <自治体名> [件数] [点数]
+                                                 保存した記述子から形の似ている自治体を探す
+    lookup <行政区域コードまたは自治体名>...     行政区域コードと自治体名を相互に引く
+    find <名前・読み・ローマ字> [件数]           名前の一部や読みから全国の自治体を探す
+                                                 （読みは組み込みの表から引く。データディレクトリに
+                                                 municipality_readings.csvがあればその読みを優先する）
+    search-prefecture <都道府県名> [件数] [点数] 形の似ている都道府県を探す
+    miniature <都道府県名> [件数] [点数]         都道府県全体の形に似ている都道府県内の自治体を探す
+    simplify <自治体名> <dp|vw> <許容誤差>...    単純化の許容誤差ごとに取り除いた頂点の数と誤差、記述子の変化を表示する
+    harmonics <自治体名> <largest|lowest> [最大個数] [再構成の点数]
+                                                 係数の個数ごとに再構成の誤差（RMSとハウスドルフ距離）を表示する
+    validate <都道府県名>                        境界の環の向き・重複頂点・自己交差を検査する
+    changes [閾値] [点数]                        データディレクトリ内の版の間で自治体の新設・廃止・形状の変化を調べる
+
+options:
+    --strategy=<largest|union|hull|multi>        飛び地のまとめ方（デフォルトはlargest）
+    --projection=<lonlat|local|jprcs1..jprcs19>  投影方法（デフォルトはlocal）
+    --resampling=<index|arc|exact>               点の取り方（デフォルトはindex）
+    --samples=<点数>                             simplifyとharmonicsで境界から取る点の数（デフォルトは256）
+    --aggregate=<city|county|subpref>            政令指定都市・郡・振興局ごとに自治体を合併する（matrix/search/index）
+    --simplify=<none|dp:許容誤差|vw:面積>        リサンプリング前の単純化（投影後の座標の単位、デフォルトはnone）
+    --holes=<drop|keep|report>                   穴の扱い（デフォルトはdrop）
+    --components=<drop|keep|report>              各featureの2つ目以降のポリゴンの扱い（デフォルトはkeep）
+    --mapping=<n03|キー[+キー...][:IDのキー]>    geojsonで表示名とIDにするプロパティ（デフォルトはn03）
+    --data-dir=<ディレクトリ>                    境界データの置き場所（デフォルトは$EPICYCLE_DATA_DIRかカレントディレクトリ）
+    --vintage=<YY_YYMMDD|YYMMDD>                 境界データの版（デフォルトはファイル名から探した最新の版）";
+
+/// 位置引数と`--名前=値`形式のオプションに分けた引数
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(args: &'a [String]) -> Result<Self> {
+        let mut positional = vec![];
+        let mut options = HashMap::new();
+        for arg in args.iter() {
+            if let Some(option) = arg.strip_prefix("--") {
+                let (key, value) = option
+                    .split_once('=')
+                    .ok_or_else(|| anyhow!("option must be --name=value: {}\n{}", arg, USAGE))?;
+                options.insert(key, value);
+            } else {
+                positional.push(arg.as_str());
+            }
+        }
+        Ok(Self {
+            positional,
+            options,
+        })
+    }
+
+    /// `idx`番目の位置引数を返す。無ければ`name`を含むエラーとする。
+    fn required(&self, idx: usize, name: &str) -> Result<&'a str> {
+        self.positional
+            .get(idx)
+            .copied()
+            .ok_or_else(|| anyhow!("{} is required\n{}", name, USAGE))
+    }
+
+    /// `idx`番目の位置引数を解釈して返す。無ければ`default`を返す。
+    fn parse_or<T>(&self, idx: usize, default: T) -> Result<T>
+    where
+        T: FromStr,
+        T::Err: std::error::Error + Send + Sync + 'static,
+    {
+        match self.positional.get(idx) {
+            Some(v) => Ok(v.parse::<T>()?),
+            None => Ok(default),
+        }
+    }
+
+    /// `--data-dir`と`--vintage`のオプションからデータの置き場所と版を決める。
+    /// 指定されなかったものは環境変数あるいはファイル名から決める。
+    fn dataset(&self) -> Result<Dataset> {
+        let dataset = match self.options.get("data-dir") {
+            Some(&root) => match self.options.get("vintage") {
+                Some(vintage) => Dataset::new(root, vintage.parse()?),
+                None => Dataset::discover(root)?,
+            },
+            None => {
+                let mut dataset = Dataset::from_env()?;
+                if let Some(vintage) = self.options.get("vintage") {
+                    dataset.vintage = vintage.parse()?;
+                }
+                dataset
+            }
+        };
+        Ok(dataset)
+    }
+
+    /// `idx`番目の位置引数を点の数とし、オプションと合わせて境界形状の設定を作る。
+    fn shape_options(&self, idx: usize) -> Result<ShapeOptions> {
+        self.shape_options_with(self.parse_or(idx, DEFAULT_SAMPLE_NUM)?)
+    }
+
+    /// 位置引数を別の用途に使うコマンドのために、`--samples`のオプションを点の数として境界形状の設定を作る。
+    fn shape_options_from_samples(&self) -> Result<ShapeOptions> {
+        let sample_num = match self.options.get("samples") {
+            Some(samples) => samples.parse()?,
+            None => DEFAULT_SAMPLE_NUM,
+        };
+        self.shape_options_with(sample_num)
+    }
+
+    /// 点の数をオプションと合わせて境界形状の設定を作る。
+    fn shape_options_with(&self, sample_num: usize) -> Result<ShapeOptions> {
+        if sample_num < 2 {
+            bail!(Error::InvalidSampleCount(sample_num));
+        }
+        let mut options = ShapeOptions::new(sample_num);
+        if let Some(strategy) = self.options.get("strategy") {
+            options.strategy = strategy.parse()?;
+        }
+        if let Some(projection) = self.options.get("projection") {
+            options.projection = projection.parse()?;
+        }
+        if let Some(resampling) = self.options.get("resampling") {
+            options.resampling = resampling.parse()?;
+        }
+        if let Some(aggregate) = self.options.get("aggregate") {
+            options.aggregate = Some(aggregate.parse()?);
+        }
+        if let Some(simplification) = self.options.get("simplify") {
+            options.simplification = simplification.parse()?;
+        }
+        if let Some(holes) = self.options.get("holes") {
+            options.rings.holes = holes.parse()?;
+        }
+        if let Some(components) = self.options.get("components") {
+            options.rings.components = components.parse()?;
+        }
+        Ok(options)
+    }
+}
+
+/// 引数の最初の要素をコマンド名として対応する処理を実行する。
+pub fn run(args: &[String]) -> Result<()> {
+    let rest = Args::parse(&args[1..])?;
+    match args[0].as_str() {
+        "matrix" => run_matrix(&rest),
+        "geojson" => run_geojson(&rest),
+        "search" => run_search(&rest),
+        "index" => run_index(&rest),
+        "search-index" => run_search_index(&rest),
+        "search-prefecture" => run_search_prefecture(&rest),
+        "miniature" => run_miniature(&rest),
+        "lookup" => run_lookup(&rest),
+        "find" => run_find(&rest),
+        "simplify" => run_simplify(&rest),
+        "harmonics" => run_harmonics(&rest),
+        "validate" => run_validate(&rest),
+        "changes" => run_changes(&rest),
+        other => bail!("unknown command: {}\n{}", other, USAGE),
+    }
+}
+
+/// 都道府県内の全自治体の距離行列を計算し、`<出力ファイル名>.csv`と`<出力ファイル名>.json`に出力する。
+/// 出力ファイル名を省略した場合は都道府県名を用いる。
+fn run_matrix(args: &Args) -> Result<()> {
+    let prefecture_name = args.required(0, "prefecture name")?;
+    let output_name = args.parse_or(1, prefecture_name.to_string())?;
+    let options = args.shape_options(2)?;
+
+    let named_shapes = prefecture_municipality_shapes(&args.dataset()?, prefecture_name, &options)?
+        .into_iter()
+        .map(|shape| (shape.name.clone(), shape.outline()))
+        .collect::<Vec<_>>();
+    let matrix = DistanceMatrix::new(&named_shapes);
+    output_distance_matrix_csv(&format!("{}.csv", output_name), &matrix)?;
+    output_distance_matrix_json(&format!("{}.json", output_name), &matrix)?;
+    println!(
+        "{} municipalities written to {}.csv / {}.json",
+        matrix.labels.len(),
+        output_name,
+        output_name
+    );
+    Ok(())
+}
+
+/// 任意のGeoJSON・TopoJSON・Shapefileの全Featureについて、`--mapping`で決まる表示名ごとの距離行列を計算し、
+/// `<出力ファイル名>.csv`と`<出力ファイル名>.json`に出力する。
+fn run_geojson(args: &Args) -> Result<()> {
+    let filename = args.required(0, "GeoJSON file")?;
+    let output_name = args.required(1, "output file name")?;
+    let options = args.shape_options(2)?;
+    let mapping = match args.options.get("mapping") {
+        Some(mapping) => mapping.parse()?,
+        None => PropertyMapping::n03(),
+    };
+
+    let features = read_feature_collection(filename)?;
+    let named_shapes = mapped_feature_shapes(&features, &mapping, &options)?
+        .into_iter()
+        .map(|shape| (shape.name.clone(), shape.outline()))
+        .collect::<Vec<_>>();
+    let matrix = DistanceMatrix::new(&named_shapes);
+    output_distance_matrix_csv(&format!("{}.csv", output_name), &matrix)?;
+    output_distance_matrix_json(&format!("{}.json", output_name), &matrix)?;
+    println!(
+        "{} features written to {}.csv / {}.json",
+        matrix.labels.len(),
+        output_name,
+        output_name
+    );
+    Ok(())
+}
+
+/// 全国から指定された自治体に形の似ている自治体を探し、距離の近い順に表示する。
+fn run_search(args: &Args) -> Result<()> {
+    let muni_name = args.required(0, "municipality name")?;
+    let k = args.parse_or(1, DEFAULT_SEARCH_NUM)?;
+    let options = args.shape_options(2)?;
+
+    let result = search_similar_municipalities(&args.dataset()?, muni_name, k, &options)?;
+    for (rank, (name, distance)) in result.iter().enumerate() {
+        println!("{:>3}  {}  {:.6}", rank + 1, name, distance);
+    }
+    Ok(())
+}
+
+/// 都道府県全体の形状を比べ、指定された都道府県に形の似ている都道府県を距離の近い順に表示する。
+fn run_search_prefecture(args: &Args) -> Result<()> {
+    let prefecture_name = args.required(0, "prefecture name")?;
+    let k = args.parse_or(1, DEFAULT_SEARCH_NUM)?;
+    let options = args.shape_options(2)?;
+
+    let descriptors = all_prefecture_descriptors(&args.dataset()?, &options)?;
+    let result = search_in_descriptors(&descriptors, prefecture_name, k)?;
+    for (rank, (name, distance)) in result.iter().enumerate() {
+        println!("{:>3}  {}  {:.6}", rank + 1, name, distance);
+    }
+    Ok(())
+}
+
+/// 都道府県内の自治体のうち、都道府県全体の形状に似ているものを距離の近い順に表示する。
+fn run_miniature(args: &Args) -> Result<()> {
+    let prefecture_name = args.required(0, "prefecture name")?;
+    let k = args.parse_or(1, DEFAULT_SEARCH_NUM)?;
+    let options = args.shape_options(2)?;
+
+    let result = prefecture_miniatures(&args.dataset()?, prefecture_name, k, &options)?;
+    for (rank, (name, distance)) in result.iter().enumerate() {
+        println!("{:>3}  {}  {:.6}", rank + 1, name, distance);
+    }
+    Ok(())
+}
+
+/// 全国の自治体の記述子を計算し、インデックスとして保存する。
+fn run_index(args: &Args) -> Result<()> {
+    let output_name = args.required(0, "output file name")?;
+    let options = args.shape_options(1)?;
+
+    let index = DescriptorIndex::build(&args.dataset()?, IndexParams::new(options))?;
+    index.save(output_name)?;
+    println!(
+        "{} municipalities written to {}",
+        index.entries.len(),
+        output_name
+    );
+    Ok(())
+}
+
+/// 保存したインデックスを使い、指定された自治体に形の似ている自治体を距離の近い順に表示する。
+fn run_search_index(args: &Args) -> Result<()> {
+    let index_name = args.required(0, "index file name")?;
+    let muni_name = args.required(1, "municipality name")?;
+    let k = args.parse_or(2, DEFAULT_SEARCH_NUM)?;
+    let options = args.shape_options(3)?;
+
+    let index = DescriptorIndex::load(index_name, &IndexParams::new(options))?;
+    for (rank, (name, distance)) in index.search(muni_name, k)?.iter().enumerate() {
+        println!("{:>3}  {}  {:.6}", rank + 1, name, distance);
+    }
+    Ok(())
+}
+
+/// 全国の対応表を作り、与えられた行政区域コードあるいは自治体名それぞれについてコードと名前を表示する。
+fn run_lookup(args: &Args) -> Result<()> {
+    args.required(0, "municipality code or name")?;
+    let gazetteer = Gazetteer::load_all(&args.dataset()?)?;
+    for &query in args.positional.iter() {
+        let entry = if is_muni_code(query) {
+            gazetteer.find_by_code(query)
+        } else {
+            gazetteer.find_by_name(query)
+        };
+        match entry {
+            Some(entry) => println!("{}  {}", entry.code, entry.name),
+            None => println!("{}  (not found)", query),
+        }
+    }
+    Ok(())
+}
+
+/// 名前の一部・読み・ローマ字に一致する自治体を一致の度合いの順に表示する。
+/// 最も良く一致したものが複数ある場合はそれらを表示する。
+fn run_find(args: &Args) -> Result<()> {
+    let query = args.required(0, "name, reading or romaji")?;
+    let search_num = args.parse_or(1, DEFAULT_SEARCH_NUM)?;
+
+    let dataset = args.dataset()?;
+    let mut gazetteer = Gazetteer::load_all(&dataset)?;
+    let readings = ReadingTable::for_dataset(&dataset)?;
+    let missing = gazetteer.set_readings(&readings);
+    if !missing.is_empty() {
+        println!(
+            "# {} municipalities have no reading and are matched by name only (e.g. {} {})",
+            missing.len(),
+            missing[0].code,
+            missing[0].name
+        );
+    }
+    let search = search_names(&gazetteer, query);
+    if search.matches.is_empty() {
+        println!("{}  (not found)", query);
+        return Ok(());
+    }
+    for m in search.matches.iter().take(search_num) {
+        println!(
+            "{}  {}  {:?}  {:.3}",
+            m.entry.code, m.entry.name, m.kind, m.score
+        );
+    }
+    if let Err(err) = search.best() {
+        println!("{}", err);
+    }
+    Ok(())
+}
+
+/// データディレクトリ内の隣り合う版どうしを比べ、行政区域コードの新設・廃止と形状の変化を表示する。
+fn run_changes(args: &Args) -> Result<()> {
+    let threshold = args.parse_or(0, DEFAULT_CHANGE_THRESHOLD)?;
+    let options = args.shape_options(1)?;
+
+    let root = args.dataset()?.root;
+    for report in track_changes(&root, &options, threshold)? {
+        println!("{} -> {}", report.from, report.to);
+        for entry in report.appeared.iter() {
+            println!("  +  {}  {}", entry.code, entry.name);
+        }
+        for entry in report.disappeared.iter() {
+            println!("  -  {}  {}", entry.code, entry.name);
+        }
+        for change in report.changed.iter() {
+            println!(
+                "  ~  {}  {} -> {}  {:.6}",
+                change.code, change.old_name, change.new_name, change.distance
+            );
+        }
+    }
+    Ok(())
+}
+
+/// 自治体の境界を許容誤差を変えながら単純化し、取り除いた頂点の数・ハウスドルフ距離と、
+/// 単純化しない場合からの記述子の距離を表示する。
+fn run_simplify(args: &Args) -> Result<()> {
+    let muni_name = args.required(0, "municipality name")?;
+    let method = args.required(1, "simplification method")?;
+    args.required(2, "tolerance")?;
+    let dataset = args.dataset()?;
+    let options = ShapeOptions {
+        simplification: Simplification::None,
+        measure_simplification: true,
+        ..args.shape_options_from_samples()?
+    };
+
+    let descriptor = |options: &ShapeOptions| -> Result<_> {
+        let shape = municipality_shape_with_options(&dataset, muni_name, options)?;
+        let descriptor = FourierDescriptor::new(&shape.outline(), DESCRIPTOR_HARMONICS);
+        Ok((shape, descriptor))
+    };
+    let (_, original) = descriptor(&options)?;
+    println!("tolerance  vertices  removed  hausdorff  descriptor_distance");
+    for tolerance in args.positional[2..].iter() {
+        let options = ShapeOptions {
+            simplification: format!("{}:{}", method, tolerance).parse()?,
+            ..options.clone()
+        };
+        let (shape, simplified) = descriptor(&options)?;
+        let report = shape.simplification;
+        println!(
+            "{}  {}  {}  {:.6}  {:.6}",
+            tolerance,
+            report.original,
+            report.removed,
+            report.hausdorff.unwrap_or(0.0),
+            original.distance(&simplified)
+        );
+    }
+    Ok(())
+}
+
+/// 自治体の境界形状の係数を選び方に従って1個ずつ増やしながら再構成し、それぞれの誤差を表示する。
+fn run_harmonics(args: &Args) -> Result<()> {
+    let muni_name = args.required(0, "municipality name")?;
+    let selection = args
+        .required(1, "harmonic selection")?
+        .parse::<HarmonicSelection>()?;
+    let max_harmonics = args.parse_or(2, DEFAULT_MAX_HARMONICS)?;
+    let resolution = args.parse_or(3, DEFAULT_RECONSTRUCTION_RESOLUTION)?;
+    let options = args.shape_options_from_samples()?;
+
+    let shape = municipality_shape_with_options(&args.dataset()?, muni_name, &options)?;
+    for part in shape.reported.iter() {
+        println!(
+            "# dropped {:?} of polygon {} ({} vertices)",
+            part.kind, part.polygon_index, part.vertex_count
+        );
+    }
+    println!("harmonics  rms  hausdorff");
+    for error in reconstruction_errors(&shape.outline(), selection, max_harmonics, resolution) {
+        println!(
+            "{}  {:.6}  {:.6}",
+            error.harmonics, error.rms, error.hausdorff
+        );
+    }
+    Ok(())
+}
+
+/// 都道府県内の全featureの環を検査し、修正や問題のあった環を表示して件数をまとめる。
+fn run_validate(args: &Args) -> Result<()> {
+    let prefecture_name = args.required(0, "prefecture name")?;
+
+    let json_data = read_raw_municipalities_boundary_data(&args.dataset()?, prefecture_name)?;
+    let (mut rings, mut reoriented, mut deduplicated, mut closed, mut invalid, mut intersecting) =
+        (0, 0, 0, 0, 0, 0);
+    for mut feat in json_data.features.into_iter() {
+        let name = geo_feature_props_to_name(&feat.properties);
+        for report in normalize_feature(&mut feat) {
+            rings += 1;
+            if report.is_clean() {
+                continue;
+            }
+            for fix in report.fixes.iter() {
+                match fix {
+                    RingFix::Reoriented => reoriented += 1,
+                    RingFix::DuplicatesRemoved { .. } => deduplicated += 1,
+                    RingFix::Closed => closed += 1,
+                    RingFix::InvalidPositionsRemoved { .. } => invalid += 1,
+                }
+            }
+            if !report.self_intersections.is_empty() {
+                intersecting += 1;
+            }
+            println!(
+                "{}  polygon {} ring {}  fixes {:?}  self-intersections {}{}",
+                name,
+                report.polygon_index,
+                report.ring_index,
+                report.fixes,
+                report.self_intersections.len(),
+                if report.degenerate {
+                    "  degenerate"
+                } else {
+                    ""
+                }
+            );
+        }
+    }
+    println!(
+        "{} rings: {} reoriented, {} deduplicated, {} closed, {} with invalid positions, {} self-intersecting",
+        rings, reoriented, deduplicated, closed, invalid, intersecting
+    );
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! データの読み込みや境界形状の取得で起こりうるエラー
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unknown prefecture: {0}")]
+    UnknownPrefecture(String),
+    #[error("no prefecture name found in municipality name: {0}")]
+    PrefectureNotInName(String),
+    #[error("invalid municipality code: {0}")]
+    InvalidMunicipalityCode(String),
+    #[error("no boundary data file named N03-YY_PP_YYMMDD.geojson or N03-YY_PP_YYMMDD.shp found in {root}")]
+    DatasetNotFound { root: String },
+    #[error("boundary data file of {prefecture} not found: {path}")]
+    DataFileNotFound { prefecture: String, path: String },
+    #[error("failed to read boundary data file of {prefecture} ({path}): {source}")]
+    DataFileRead {
+        prefecture: String,
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("failed to parse boundary data file of {prefecture} ({path}): {source}")]
+    DataFileParse {
+        prefecture: String,
+        path: String,
+        source: serde_json::Error,
+    },
+    #[error("municipality {name} not found in {location}")]
+    MunicipalityNotFound { name: String, location: String },
+    #[error("{query} matches several municipalities equally: {candidates:?}")]
+    AmbiguousName {
+        query: String,
+        candidates: Vec<String>,
+    },
+    #[error("invalid municipality readings file {path} at line {line}: {reason}")]
+    InvalidReadings {
+        path: String,
+        line: usize,
+        reason: String,
+    },
+    #[error("failed to parse shapefile {path}: {reason}")]
+    InvalidShapefile { path: String, reason: String },
+    #[error("invalid TopoJSON object {object}: {reason}")]
+    InvalidTopology { object: String, reason: String },
+    #[error("{name} has no polygon")]
+    NoPolygon { name: String },
+    #[error("number of points must be at least 2: {0}")]
+    InvalidSampleCount(usize),
+    #[error("cannot resample a degenerate ring of {vertices} vertices at a single point")]
+    DegenerateRing { vertices: usize },
+    #[error("unknown {kind}: {value}")]
+    InvalidOption { kind: &'static str, value: String },
+    #[error("index {path} cannot be used: {reason}")]
+    IndexMismatch { path: String, reason: String },
+    #[error("failed to access {path}: {source}")]
+    Io {
+        path: String,
+        source: std::io::Error,
+    },
+    #[error("failed to (de)serialize {path}: {source}")]
+    Json {
+        path: String,
+        source: serde_json::Error,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// ファイル操作のエラーにファイル名を付ける。
+    pub fn io(path: &str) -> impl FnOnce(std::io::Error) -> Self + '_ {
+        move |source| Self::Io {
+            path: path.to_string(),
+            source,
+        }
+    }
+
+    /// JSONの読み書きのエラーにファイル名を付ける。
+    pub fn json(path: &str) -> impl FnOnce(serde_json::Error) -> Self + '_ {
+        move |source| Self::Json {
+            path: path.to_string(),
+            source,
+        }
+    }
+}
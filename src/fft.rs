@@ -1,4 +1,12 @@
+use std::str::FromStr;
+
 use rustfft::{num_complex::Complex, FftPlanner};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    municipalities::dataset::Dataset,
+};
 
 #[allow(unused)]
 use crate::shapes::municipality_shape;
@@ -7,24 +15,84 @@ use crate::shapes::{flower, rectangle, simple_circle, ShapePoints};
 
 /// 二次元図形を複素数で表現して与える。
 /// 系列の長さは2の冪であるものとする。
-pub fn create_shape() -> ShapePoints {
-    // rectangle()
-    // simple_circle()
-    // flower()
-    municipality_shape("兵庫県丹波篠山市", 256)
+pub fn create_shape() -> Result<ShapePoints> {
+    // Ok(rectangle())
+    // Ok(simple_circle())
+    // Ok(flower())
+    municipality_shape(&Dataset::from_env()?, "兵庫県丹波篠山市", 256)
+}
+
+/// 順方向のFFTの結果に掛ける係数の種類
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FftNormalization {
+    /// 正規化しない。係数の大きさは円の半径の`N`倍になる
+    #[default]
+    None,
+    /// `1/N`を掛ける。係数の大きさがそのまま円の半径になる
+    ByLength,
+    /// `1/√N`を掛ける。変換の前後でエネルギー（二乗和）が保たれる
+    Unitary,
+}
+
+impl FftNormalization {
+    /// 長さ`len`の点列の順方向のFFTの結果に掛ける係数
+    pub fn forward_factor(self, len: usize) -> f64 {
+        match self {
+            Self::None => 1.0,
+            Self::ByLength => 1.0 / len as f64,
+            Self::Unitary => 1.0 / (len as f64).sqrt(),
+        }
+    }
+
+    /// この正規化の係数に掛けると円の半径（`1/N`で正規化した係数）になる値
+    pub fn radius_factor(self, len: usize) -> f64 {
+        match self {
+            Self::None => 1.0 / len as f64,
+            Self::ByLength => 1.0,
+            Self::Unitary => 1.0 / (len as f64).sqrt(),
+        }
+    }
 }
 
-/// 座標点列を複素関数と解釈してFFTを適用する。
-pub fn fft_points(points: &[Complex<f64>]) -> Vec<Complex<f64>> {
+/// `none`、`1/n`、`1/sqrt(n)`のいずれかの文字列から正規化の種類を作る。
+impl FromStr for FftNormalization {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Self::None),
+            "1/n" => Ok(Self::ByLength),
+            "1/sqrt(n)" => Ok(Self::Unitary),
+            other => Err(Error::InvalidOption {
+                kind: "fft normalization",
+                value: other.to_string(),
+            }),
+        }
+    }
+}
+
+/// 座標点列を複素関数と解釈してFFTを適用し、`normalization`に従って正規化する。
+pub fn fft_points(points: &[Complex<f64>], normalization: FftNormalization) -> Vec<Complex<f64>> {
     let mut planner = FftPlanner::<f64>::new();
     let points_num = points.len();
     let fft = planner.plan_fft_forward(points_num);
     let mut buffer = points.to_owned();
     fft.process(&mut buffer);
-    // 正規化を適用しない。する場合はこのコメントを外す
-    // for v in buffer.iter_mut() {
-    //     *v /= points_num as f64;
-    // }
+    let factor = normalization.forward_factor(points_num);
+    if factor != 1.0 {
+        for v in buffer.iter_mut() {
+            *v *= factor;
+        }
+    }
+    buffer
+}
+
+/// 周波数成分の列に逆FFTを適用する。正規化は適用しない。
+pub fn ifft_points(bins: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let mut planner = FftPlanner::<f64>::new();
+    let ifft = planner.plan_fft_inverse(bins.len());
+    let mut buffer = bins.to_owned();
+    ifft.process(&mut buffer);
     buffer
 }
 
@@ -0,0 +1,282 @@
+//! 複素数で表した平面上の多角形に対する幾何演算
+
+use std::collections::HashMap;
+
+use rustfft::num_complex::Complex;
+
+/// 辺の端点を突き合わせる際の座標の量子化の細かさ
+const VERTEX_KEY_SCALE: f64 = 1e9;
+
+type VertexKey = (i64, i64);
+
+fn vertex_key(p: Complex<f64>) -> VertexKey {
+    (
+        (p.re * VERTEX_KEY_SCALE).round() as i64,
+        (p.im * VERTEX_KEY_SCALE).round() as i64,
+    )
+}
+
+/// 最後の点が最初の点と一致していればそれを除いた点列を返す。
+pub fn open_ring(ring: &[Complex<f64>]) -> &[Complex<f64>] {
+    match ring {
+        [first, rest @ .., last] if first == last => &ring[..rest.len() + 1],
+        _ => ring,
+    }
+}
+
+/// 最後の点を最初の点と一致させた点列を返す。
+pub fn close_ring(mut ring: Vec<Complex<f64>>) -> Vec<Complex<f64>> {
+    if let (Some(&first), Some(&last)) = (ring.first(), ring.last()) {
+        if first != last {
+            ring.push(first);
+        }
+    }
+    ring
+}
+
+/// 環の符号付き面積を返す。反時計回りなら正になる。
+pub fn signed_area(ring: &[Complex<f64>]) -> f64 {
+    let ring = open_ring(ring);
+    let n = ring.len();
+    (0..n)
+        .map(|i| {
+            let (a, b) = (ring[i], ring[(i + 1) % n]);
+            a.re * b.im - b.re * a.im
+        })
+        .sum::<f64>()
+        / 2.0
+}
+
+/// 点が環の内側にあるかどうかを偶奇規則で返す。
+pub fn contains_point(ring: &[Complex<f64>], p: Complex<f64>) -> bool {
+    let ring = open_ring(ring);
+    let n = ring.len();
+    let mut inside = false;
+    for i in 0..n {
+        let (a, b) = (ring[i], ring[(i + 1) % n]);
+        if (a.im > p.im) != (b.im > p.im) {
+            let x = a.re + (p.im - a.im) / (b.im - a.im) * (b.re - a.re);
+            if p.re < x {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+/// 環の周長を返す。
+pub fn perimeter(ring: &[Complex<f64>]) -> f64 {
+    let ring = open_ring(ring);
+    let n = ring.len();
+    (0..n).map(|i| (ring[(i + 1) % n] - ring[i]).norm()).sum()
+}
+
+/// 点`p`から線分`ab`までの距離
+pub fn segment_distance(p: Complex<f64>, a: Complex<f64>, b: Complex<f64>) -> f64 {
+    let ab = b - a;
+    let length_sqr = ab.norm_sqr();
+    if length_sqr == 0.0 {
+        return (p - a).norm();
+    }
+    let t = ((p - a) * ab.conj()).re / length_sqr;
+    (p - (a + ab * t.clamp(0.0, 1.0))).norm()
+}
+
+/// 点`p`から環の境界までの距離を返す。
+pub fn ring_distance(p: Complex<f64>, ring: &[Complex<f64>]) -> f64 {
+    let ring = open_ring(ring);
+    let n = ring.len();
+    (0..n)
+        .map(|i| segment_distance(p, ring[i], ring[(i + 1) % n]))
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// 二つの環の境界の間のハウスドルフ距離を返す。
+/// 一方の各頂点から他方の境界までの距離の最大値を両方向について求め、大きい方とする。
+pub fn hausdorff_distance(a: &[Complex<f64>], b: &[Complex<f64>]) -> f64 {
+    let directed = |from: &[Complex<f64>], to: &[Complex<f64>]| {
+        from.iter()
+            .map(|&p| ring_distance(p, to))
+            .fold(0.0, f64::max)
+    };
+    directed(a, b).max(directed(b, a))
+}
+
+/// 反時計回りに揃えた閉じた環を返す。
+pub fn counter_clockwise(ring: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let mut ring = open_ring(ring).to_vec();
+    if signed_area(&ring) < 0.0 {
+        ring.reverse();
+    }
+    close_ring(ring)
+}
+
+/// 点群の凸包を反時計回りの閉じた環として返す（Andrewのモノトーンチェーン法）。
+pub fn convex_hull(points: &[Complex<f64>]) -> Vec<Complex<f64>> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.re.total_cmp(&b.re).then(a.im.total_cmp(&b.im)));
+    sorted.dedup();
+    if sorted.len() < 3 {
+        return close_ring(sorted);
+    }
+    let cross = |o: Complex<f64>, a: Complex<f64>, b: Complex<f64>| {
+        (a - o).re * (b - o).im - (a - o).im * (b - o).re
+    };
+    let mut hull: Vec<Complex<f64>> = vec![];
+    // 下側と上側をそれぞれ構成する
+    for iter in [
+        Box::new(sorted.iter()) as Box<dyn Iterator<Item = _>>,
+        Box::new(sorted.iter().rev()),
+    ] {
+        let start_len = hull.len();
+        for &p in iter {
+            while hull.len() >= start_len + 2
+                && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.0
+            {
+                hull.pop();
+            }
+            hull.push(p);
+        }
+        // 次の連鎖の始点と重複するので除く
+        hull.pop();
+    }
+    close_ring(hull)
+}
+
+/// 複数の環を合併し、共有する境界を取り除いた環の列を返す。
+/// 隣り合う環が頂点を共有している（境界が位相的に一致している）ことを前提とし、
+/// 互いに逆向きに現れる辺を打ち消してから残った辺をつなぎ直す。
+/// 結果の外周は反時計回り、穴は時計回りになる。
+pub fn dissolve(rings: &[Vec<Complex<f64>>]) -> Vec<Vec<Complex<f64>>> {
+    // 向きを揃えた上で有向辺を数える
+    let mut edges = HashMap::<(VertexKey, VertexKey), (Complex<f64>, Complex<f64>, usize)>::new();
+    let mut edge_order = vec![];
+    for ring in rings.iter() {
+        let ring = counter_clockwise(ring);
+        for pair in ring.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let key = (vertex_key(a), vertex_key(b));
+            if key.0 == key.1 {
+                continue;
+            }
+            let reverse_key = (key.1, key.0);
+            match edges.get_mut(&reverse_key) {
+                Some(entry) if entry.2 > 0 => entry.2 -= 1,
+                _ => {
+                    let entry = edges.entry(key).or_insert((a, b, 0));
+                    entry.2 += 1;
+                    edge_order.push(key);
+                }
+            }
+        }
+    }
+    // 残った辺を始点ごとにまとめる（出現順を保つ）
+    let mut outgoing = HashMap::<VertexKey, Vec<(VertexKey, Complex<f64>, Complex<f64>)>>::new();
+    for key in edge_order {
+        if let Some(entry) = edges.get_mut(&key) {
+            if entry.2 > 0 {
+                entry.2 -= 1;
+                outgoing
+                    .entry(key.0)
+                    .or_default()
+                    .push((key.1, entry.0, entry.1));
+            }
+        }
+    }
+    // 辺をたどって環を組み立てる
+    let mut starts = outgoing.keys().copied().collect::<Vec<_>>();
+    starts.sort();
+    let mut result = vec![];
+    for start in starts {
+        while let Some((mut next, a, mut b)) = outgoing.get_mut(&start).and_then(|v| v.pop()) {
+            let mut ring = vec![a, b];
+            while next != start {
+                let Some((following, _, end)) = outgoing.get_mut(&next).and_then(|v| v.pop())
+                else {
+                    break;
+                };
+                next = following;
+                b = end;
+                ring.push(b);
+            }
+            if ring.len() >= 4 {
+                result.push(close_ring(ring));
+            }
+        }
+    }
+    result
+}
+
+/// 複数の環を幅0の橋でつないで一つの閉じた点列にする。
+/// 面積の大きい環から順に、既存の点列のうち環の重心に最も近い点と、その点に最も近い環の点とを結ぶ。
+pub fn bridge_rings(rings: &[Vec<Complex<f64>>]) -> Vec<Complex<f64>> {
+    let mut sorted = rings
+        .iter()
+        .map(|r| open_ring(r).to_vec())
+        .filter(|r| !r.is_empty())
+        .collect::<Vec<_>>();
+    sorted.sort_by(|a, b| signed_area(b).abs().total_cmp(&signed_area(a).abs()));
+    let mut iter = sorted.into_iter();
+    let Some(mut contour) = iter.next() else {
+        return vec![];
+    };
+    let nearest = |points: &[Complex<f64>], target: Complex<f64>| {
+        points
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| (*a - target).norm().total_cmp(&(*b - target).norm()))
+            .map(|(idx, _)| idx)
+            .unwrap()
+    };
+    for ring in iter {
+        let centroid = ring.iter().sum::<Complex<f64>>() / ring.len() as f64;
+        let contour_idx = nearest(&contour, centroid);
+        let ring_idx = nearest(&ring, contour[contour_idx]);
+        // contour[..=i] -> ring[j..] -> ring[..=j] -> contour[i..]
+        let mut spliced = contour[..=contour_idx].to_vec();
+        spliced.extend(ring[ring_idx..].iter().chain(&ring[..=ring_idx]));
+        spliced.extend(&contour[contour_idx..]);
+        contour = spliced;
+    }
+    close_ring(contour)
+}
+
+/// 面積・凸包・合併の基本的な性質を確かめる。
+#[test]
+fn test_polygon_operations() {
+    let c = |re: f64, im: f64| Complex::new(re, im);
+    let square = vec![
+        c(0.0, 0.0),
+        c(1.0, 0.0),
+        c(1.0, 1.0),
+        c(0.0, 1.0),
+        c(0.0, 0.0),
+    ];
+    assert_eq!(signed_area(&square), 1.0);
+    assert_eq!(perimeter(&square), 4.0);
+    let reversed = square.iter().rev().copied().collect::<Vec<_>>();
+    assert_eq!(signed_area(&reversed), -1.0);
+    assert_eq!(signed_area(&counter_clockwise(&reversed)), 1.0);
+
+    // 内部の点は凸包に含まれない
+    let mut points = square.clone();
+    points.push(c(0.5, 0.5));
+    let hull = convex_hull(&points);
+    assert_eq!(hull.len(), 5);
+    assert_eq!(signed_area(&hull), 1.0);
+
+    // 辺を共有する二つの正方形は一つの長方形になる
+    let right = square.iter().map(|p| p + 1.0).collect::<Vec<_>>();
+    let dissolved = dissolve(&[square.clone(), reversed.iter().map(|p| p + 1.0).collect()]);
+    assert_eq!(dissolved.len(), 1);
+    assert_eq!(signed_area(&dissolved[0]), 2.0);
+    assert_eq!(perimeter(&dissolved[0]), 6.0);
+    assert_eq!(dissolve(&[square.clone(), right]).len(), 1);
+
+    // 離れた二つの正方形は橋でつながれ、面積は合計になる
+    let far = square.iter().map(|p| p + 3.0).collect::<Vec<_>>();
+    assert_eq!(dissolve(&[square.clone(), far.clone()]).len(), 2);
+    let bridged = bridge_rings(&[square, far]);
+    assert_eq!(bridged.first(), bridged.last());
+    assert!((signed_area(&bridged) - 2.0).abs() < 1e-12);
+}
@@ -1,12 +1,26 @@
-use nannou::{
-    color::{Hue, IntoLinSrgba},
-    draw::properties::ColorScalar,
-    prelude::*,
+use std::sync::OnceLock;
+
+use nannou::{color::IntoLinSrgba, draw::properties::ColorScalar, prelude::*};
+
+use crate::{
+    error::Result,
+    fft::{create_shape, FftNormalization},
+    shapes::ShapePoints,
+    spectrum::{Spectrum, TruncationPolicy},
 };
 
-use crate::fft::{create_shape, fft_points};
+/// 描く円の減らし方
+const TRUNCATION_POLICY: TruncationPolicy = TruncationPolicy::Energy(99.9);
 
-const LOW_PASS_RATE: f32 = 0.5;
+/// 描く形状。`model`は引数を取れないので、ウィンドウを開く前に`prepare_shape`で作っておく。
+static SHAPE: OnceLock<ShapePoints> = OnceLock::new();
+
+/// 描く形状を作って保持する。作れなければウィンドウを開く前にエラーを返す。
+pub fn prepare_shape() -> Result<()> {
+    let shape = create_shape()?;
+    SHAPE.get_or_init(|| shape);
+    Ok(())
+}
 
 /// 円を描く
 fn draw_circle<C>(draw: &Draw, center: Vec2, radius: f32, fill: bool, color: C)
@@ -32,7 +46,8 @@ fn draw_phase_circle(draw: &Draw, center: Vec2, radius: f32, phase: f32) {
 }
 
 struct FFTResult {
-    freq: u32,
+    /// 符号付きの周波数。負なら時計回りに回る
+    freq: i64,
     abs: f32,
     arg: f32,
 }
@@ -42,7 +57,7 @@ pub struct Model {
     fg_color: Hsl,
     // もとの点列の長さ。変更されない。
     raw_seq_len: usize,
-    // 削減後の点列の長さ。変更されない。
+    // 削減後の円の数。変更されない。
     #[allow(unused)]
     seq_len: usize,
     // 形状を表す点列。変更されない。
@@ -61,17 +76,20 @@ pub struct Model {
 
 pub fn model(app: &App) -> Model {
     let _window = app.new_window().view(view).build().unwrap();
-    let shape_points = create_shape();
+    let shape_points = SHAPE
+        .get()
+        .cloned()
+        .expect("prepare_shape must be called before the app starts");
     // 点の数を計算
     let raw_seq_len = shape_points.len();
-    let seq_len = (LOW_PASS_RATE * raw_seq_len as f32) as usize;
-    // FFTした上で大きさ降順にソートし、影響の小さい円から指定された割合だけ削る
-    let mut fft_result = fft_points(&shape_points)
-        .into_iter()
-        .enumerate()
-        .collect::<Vec<_>>();
-    fft_result.sort_by(|(_, a), (_, b)| b.norm().total_cmp(&a.norm()));
-    let fft_result = fft_result.into_iter().take(seq_len).collect::<Vec<_>>();
+    // FFTした上で大きさ降順に並べ、影響の小さい円を設定に従って削る
+    let (spectrum, report) =
+        Spectrum::new(&shape_points, FftNormalization::ByLength).truncate(TRUNCATION_POLICY);
+    println!(
+        "circles: {} / {} (energy {:.4}, max |freq| {})",
+        report.kept, report.total, report.energy_ratio, report.max_frequency
+    );
+    let seq_len = report.kept;
     // 複素点列で表された形状をVec2に変換しておく
     let shape_points_vec2 = shape_points
         .into_iter()
@@ -81,13 +99,13 @@ pub fn model(app: &App) -> Model {
     let mut fft_results: Vec<FFTResult> = vec![];
     let mut circle_centers: Vec<Vec2> = vec![];
     let mut center = Vec2::ZERO;
-    for (freq, c) in fft_result.into_iter() {
-        let abs = c.norm() as f32 / raw_seq_len as f32;
-        let arg = c.arg() as f32;
+    for coefficient in spectrum.coefficients.iter() {
+        // 半径と中心のずれはどちらも同じ換算をした係数から求める
+        let c = spectrum.amplitude(coefficient.value);
         fft_results.push(FFTResult {
-            freq: freq as u32,
-            abs,
-            arg,
+            freq: coefficient.freq,
+            abs: c.norm() as f32,
+            arg: c.arg() as f32,
         });
         circle_centers.push(center);
         center += pt2(c.re as f32, c.im as f32);
@@ -0,0 +1,222 @@
+//! 全自治体の記述子を事前に計算してファイルに保存し、GeoJSONを読まずに類似検索できるようにする
+
+use std::{
+    fs::{metadata, read_to_string, File},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    municipalities::{data::PREFECTURES, dataset::Dataset},
+    search::{all_municipality_descriptors, search_in_descriptors},
+    shapes::ShapeOptions,
+    similarity::{FourierDescriptor, DESCRIPTOR_HARMONICS, DESCRIPTOR_NORMALIZATION},
+};
+
+/// インデックスファイルの形式のバージョン。形式や記述子の値が変わったら上げる。
+/// 2: 読み込み時に環の向きをそろえるようになり、向きの逆だった自治体の正負の周波数の大きさが入れ替わった。
+pub const INDEX_VERSION: u32 = 2;
+
+/// 記述子の計算条件。保存時と読み込み時で一致しなければならない。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IndexParams {
+    /// 境界形状の取得に用いた設定（点の数や飛び地のまとめ方）
+    pub shape_options: ShapeOptions,
+    /// 記述子の周波数の上限
+    pub harmonics: usize,
+    /// 記述子の正規化方法
+    pub normalization: String,
+}
+
+impl IndexParams {
+    /// 境界形状の設定を指定し、それ以外は現在の記述子の計算方法に合わせた条件を作る。
+    pub fn new(shape_options: ShapeOptions) -> Self {
+        Self {
+            shape_options,
+            harmonics: DESCRIPTOR_HARMONICS,
+            normalization: DESCRIPTOR_NORMALIZATION.to_string(),
+        }
+    }
+}
+
+/// 記述子の元になったファイルの情報。ファイルが更新されていないかの確認に使う。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub name: String,
+    pub size: u64,
+    /// 最終更新時刻（UNIX時間の秒）
+    pub modified: u64,
+}
+
+impl SourceFile {
+    /// 現在のファイルの情報を取得する。
+    fn current(name: &str) -> Result<Self> {
+        let meta = metadata(name).map_err(Error::io(name))?;
+        let modified = meta
+            .modified()
+            .map_err(Error::io(name))?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        Ok(Self {
+            name: name.to_string(),
+            size: meta.len(),
+            modified,
+        })
+    }
+
+    /// 境界データのファイルと、Shapefileの場合は属性を持つ.dbf（と、あれば符号化方式を示す.cpg）の情報を取得する。
+    fn of_boundary_data(path: &Path) -> Result<Vec<Self>> {
+        let mut paths = vec![path.to_path_buf()];
+        if path.extension().is_some_and(|ext| ext == "shp") {
+            paths.push(path.with_extension("dbf"));
+            let cpg = path.with_extension("cpg");
+            if cpg.exists() {
+                paths.push(cpg);
+            }
+        }
+        paths
+            .iter()
+            .map(|path| Self::current(&path.display().to_string()))
+            .collect()
+    }
+}
+
+/// 自治体名と記述子の組を保持するインデックス。
+#[derive(Serialize, Deserialize, Debug)]
+pub struct DescriptorIndex {
+    pub version: u32,
+    pub params: IndexParams,
+    pub sources: Vec<SourceFile>,
+    pub entries: Vec<(String, FourierDescriptor)>,
+}
+
+impl DescriptorIndex {
+    /// `dataset`の全都道府県のファイルを読み込んでインデックスを作る。
+    pub fn build(dataset: &Dataset, params: IndexParams) -> Result<Self> {
+        if params != IndexParams::new(params.shape_options.clone()) {
+            return Err(Error::IndexMismatch {
+                path: "(new index)".to_string(),
+                reason: format!("parameters other than the current ones: {:?}", params),
+            });
+        }
+        let mut sources = vec![];
+        for pref in PREFECTURES.iter().skip(1) {
+            sources.extend(SourceFile::of_boundary_data(
+                &dataset.boundary_data_path(pref)?,
+            )?);
+        }
+        let entries = all_municipality_descriptors(dataset, &params.shape_options)?;
+        Ok(Self {
+            version: INDEX_VERSION,
+            params,
+            sources,
+            entries,
+        })
+    }
+
+    /// インデックスをJSON形式でファイルに保存する。
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let file = File::create(filename).map_err(Error::io(filename))?;
+        serde_json::to_writer(file, self).map_err(Error::json(filename))?;
+        Ok(())
+    }
+
+    /// ファイルからインデックスを読み込む。
+    /// バージョンや計算条件が`params`と異なる場合、元のファイルが無くなったり更新されたりしている場合はエラーとする。
+    pub fn load(filename: &str, params: &IndexParams) -> Result<Self> {
+        let content = read_to_string(filename).map_err(Error::io(filename))?;
+        let index = serde_json::from_str::<Self>(&content).map_err(Error::json(filename))?;
+        let mismatch = |reason: String| Error::IndexMismatch {
+            path: filename.to_string(),
+            reason,
+        };
+        if index.version != INDEX_VERSION {
+            return Err(mismatch(format!(
+                "version {} is given, but version {} is required",
+                index.version, INDEX_VERSION
+            )));
+        }
+        if &index.params != params {
+            return Err(mismatch(format!(
+                "built with {:?}, but {:?} is required",
+                index.params, params
+            )));
+        }
+        for source in index.sources.iter() {
+            match SourceFile::current(&source.name) {
+                Ok(current) if &current == source => {}
+                Ok(_) => return Err(mismatch(format!("{} was modified", source.name))),
+                Err(_) => return Err(mismatch(format!("{} is missing", source.name))),
+            }
+        }
+        Ok(index)
+    }
+
+    /// インデックス内で`muni_name`に形状が似ている自治体を`k`個、距離とともに返す。
+    pub fn search(&self, muni_name: &str, k: usize) -> Result<Vec<(String, f64)>> {
+        search_in_descriptors(&self.entries, muni_name, k)
+    }
+}
+
+/// 保存したインデックスが読み込めること、条件の異なるインデックスや古いインデックスを拒否することを確かめる。
+#[test]
+fn test_index_save_and_load() {
+    use std::fs::write;
+
+    use crate::{
+        shapes::{flower, rectangle, MergeStrategy},
+        test::TempDir,
+    };
+
+    let dir = TempDir::new("index_save_and_load");
+    let source_path = dir.path().join("source.shp");
+    let source_name = source_path.to_str().unwrap();
+    let dbf_path = source_path.with_extension("dbf");
+    write(source_name, "source").unwrap();
+    write(&dbf_path, "attributes").unwrap();
+    let index_name = dir.path().join("index.json");
+    let index_name = index_name.to_str().unwrap();
+
+    let params = IndexParams::new(ShapeOptions::new(64));
+    let index = DescriptorIndex {
+        version: INDEX_VERSION,
+        params: params.clone(),
+        sources: SourceFile::of_boundary_data(&source_path).unwrap(),
+        entries: vec![
+            (
+                "flower".to_string(),
+                FourierDescriptor::new(&flower(), DESCRIPTOR_HARMONICS),
+            ),
+            (
+                "rectangle".to_string(),
+                FourierDescriptor::new(&rectangle(), DESCRIPTOR_HARMONICS),
+            ),
+        ],
+    };
+    index.save(index_name).unwrap();
+
+    let loaded = DescriptorIndex::load(index_name, &params).unwrap();
+    for ((loaded_name, loaded_desc), (name, desc)) in loaded.entries.iter().zip(&index.entries) {
+        assert_eq!(loaded_name, name);
+        assert!(loaded_desc.distance(desc) < 1e-12);
+    }
+    let result = loaded.search("flower", 1).unwrap();
+    assert_eq!(result[0].0, "rectangle");
+
+    // 点の数や飛び地のまとめ方が異なる
+    let other_params = IndexParams::new(ShapeOptions::new(128));
+    assert!(DescriptorIndex::load(index_name, &other_params).is_err());
+    let mut other_options = ShapeOptions::new(64);
+    other_options.strategy = MergeStrategy::Union;
+    assert!(DescriptorIndex::load(index_name, &IndexParams::new(other_options)).is_err());
+    // 元のファイルが更新された（.shpと対になる.dbfの更新も検出する）
+    assert_eq!(index.sources.len(), 2);
+    write(&dbf_path, "modified attributes").unwrap();
+    assert!(DescriptorIndex::load(index_name, &params).is_err());
+    write(source_name, "modified source").unwrap();
+    assert!(DescriptorIndex::load(index_name, &params).is_err());
+}
@@ -1,19 +1,28 @@
-use anyhow::Result;
 use std::fmt::Display;
 use std::fs::{read_to_string, File};
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 
-use crate::municipalities::data::PREFECTURES;
-use crate::municipalities::serde_models::GeoJson;
+use serde::Deserialize;
+
+use crate::error::{Error, Result};
+use crate::municipalities::dataset::Dataset;
+use crate::municipalities::serde_models::{Crs, CrsProp, FeatureCollection, GeoFeature, GeoJson};
+use crate::municipalities::shapefile::read_shapefile;
+use crate::municipalities::topojson::Topology;
+use crate::municipalities::validation::normalize_geometry;
+use crate::similarity::DistanceMatrix;
+
+/// 国土数値情報の行政区域データの座標参照系（JGD2011の経緯度）
+const N03_CRS: &str = "urn:ogc:def:crs:EPSG::6668";
 
 #[allow(unused)]
 pub fn output_sequences<T>(filename: &str, data: &[T]) -> Result<()>
 where
     T: Display,
 {
-    let mut file = File::create(filename)?;
+    let mut file = File::create(filename).map_err(Error::io(filename))?;
     for v in data {
-        writeln!(file, "{}", v)?;
+        writeln!(file, "{}", v).map_err(Error::io(filename))?;
     }
     Ok(())
 }
@@ -23,9 +32,9 @@ pub fn output_sequences_with_x<T>(filename: &str, x_data: &[f64], data: &[T]) ->
 where
     T: Display,
 {
-    let mut file = File::create(filename)?;
+    let mut file = File::create(filename).map_err(Error::io(filename))?;
     for (x, y) in x_data.iter().zip(data) {
-        writeln!(file, "{}  {}", x, y)?;
+        writeln!(file, "{}  {}", x, y).map_err(Error::io(filename))?;
     }
     Ok(())
 }
@@ -35,23 +44,114 @@ pub fn output_2d_sequences<T>(filename: &str, xy_data: &[[T; 2]]) -> Result<()>
 where
     T: Display,
 {
-    let mut file = File::create(filename)?;
+    let mut file = File::create(filename).map_err(Error::io(filename))?;
     for [x, y] in xy_data.iter() {
-        writeln!(file, "{}  {}", x, y)?;
+        writeln!(file, "{}  {}", x, y).map_err(Error::io(filename))?;
+    }
+    Ok(())
+}
+
+/// 距離行列をCSV形式で出力する。1行目と1列目は形状のラベルとする。
+pub fn output_distance_matrix_csv(filename: &str, matrix: &DistanceMatrix) -> Result<()> {
+    let mut file = File::create(filename).map_err(Error::io(filename))?;
+    writeln!(file, ",{}", matrix.labels.join(",")).map_err(Error::io(filename))?;
+    for (label, row) in matrix.labels.iter().zip(&matrix.distances) {
+        let row = row.iter().map(|d| d.to_string()).collect::<Vec<_>>();
+        writeln!(file, "{},{}", label, row.join(",")).map_err(Error::io(filename))?;
     }
     Ok(())
 }
 
-/// 都道府県名を指定して対応するファイルのデータを読み込む。
-pub fn read_municipalities_boundary_data(prefecture_name: &str) -> Result<GeoJson> {
-    let id = PREFECTURES
-        .iter()
-        .enumerate()
-        .find(|(_, &pref)| pref == prefecture_name)
-        .unwrap()
-        .0;
-    let filename = format!("N03-23_{}_230101.geojson", id);
-    let json_content = read_to_string(filename)?;
-    let json_obj = serde_json::from_str::<GeoJson>(&json_content)?;
+/// 距離行列をJSON形式で出力する。
+pub fn output_distance_matrix_json(filename: &str, matrix: &DistanceMatrix) -> Result<()> {
+    let file = File::create(filename).map_err(Error::io(filename))?;
+    serde_json::to_writer_pretty(file, matrix).map_err(Error::json(filename))?;
+    Ok(())
+}
+
+/// 都道府県名を指定して`dataset`内の対応するファイルのデータを読み込み、全ての環を正規化する。
+/// Shapefileの場合もGeoJSONと同じ形にして返す。
+pub fn read_municipalities_boundary_data(
+    dataset: &Dataset,
+    prefecture_name: &str,
+) -> Result<GeoJson> {
+    let mut json_obj = read_raw_municipalities_boundary_data(dataset, prefecture_name)?;
+    json_obj.features.iter_mut().for_each(normalize_geometry);
     Ok(json_obj)
 }
+
+/// `read_municipalities_boundary_data`と同じく読み込むが、環を正規化せずにファイルの内容のまま返す。
+pub fn read_raw_municipalities_boundary_data(
+    dataset: &Dataset,
+    prefecture_name: &str,
+) -> Result<GeoJson> {
+    let path = dataset.boundary_data_path(prefecture_name)?;
+    let filename = path.display().to_string();
+    if path.extension().is_some_and(|ext| ext == "shp") {
+        return Ok(GeoJson {
+            _type: "FeatureCollection".to_string(),
+            name: path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            crs: Crs {
+                _type: "name".to_string(),
+                properties: CrsProp {
+                    name: N03_CRS.to_string(),
+                },
+            },
+            features: read_shapefile(&path)?,
+        });
+    }
+    let json_content = read_to_string(&path).map_err(|source| match source.kind() {
+        ErrorKind::NotFound => Error::DataFileNotFound {
+            prefecture: prefecture_name.to_string(),
+            path: filename.clone(),
+        },
+        _ => Error::DataFileRead {
+            prefecture: prefecture_name.to_string(),
+            path: filename.clone(),
+            source,
+        },
+    })?;
+    let json_obj =
+        serde_json::from_str::<GeoJson>(&json_content).map_err(|source| Error::DataFileParse {
+            prefecture: prefecture_name.to_string(),
+            path: filename.clone(),
+            source,
+        })?;
+    Ok(json_obj)
+}
+
+/// JSONの最上位の`type`フィールドのみを読む
+#[derive(Deserialize)]
+struct JsonType {
+    #[serde(rename = "type")]
+    _type: String,
+}
+
+/// 任意のGeoJSONのFeatureCollectionを読み込み、ジオメトリを持つFeatureを返す。
+/// 拡張子が`.shp`の場合はShapefileとして、`type`が`Topology`の場合はTopoJSONとして読み込む。
+/// TopoJSONの場合は全てのオブジェクトを名前の順に展開する。いずれの場合も全ての環を正規化する。
+pub fn read_feature_collection(filename: &str) -> Result<Vec<GeoFeature>> {
+    let mut features = read_raw_feature_collection(filename)?;
+    features.iter_mut().for_each(normalize_geometry);
+    Ok(features)
+}
+
+fn read_raw_feature_collection(filename: &str) -> Result<Vec<GeoFeature>> {
+    if filename.ends_with(".shp") {
+        return read_shapefile(std::path::Path::new(filename));
+    }
+    let json_content = read_to_string(filename).map_err(Error::io(filename))?;
+    let json_type =
+        serde_json::from_str::<JsonType>(&json_content).map_err(Error::json(filename))?;
+    if json_type._type == "Topology" {
+        let topology =
+            serde_json::from_str::<Topology>(&json_content).map_err(Error::json(filename))?;
+        return topology.features(None);
+    }
+    let collection =
+        serde_json::from_str::<FeatureCollection>(&json_content).map_err(Error::json(filename))?;
+    Ok(collection.into_geo_features())
+}
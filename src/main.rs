@@ -1,26 +1,43 @@
+mod changes;
+mod commands;
+mod error;
 mod fft;
+mod geometry;
 mod graph;
+mod index;
 mod io;
 mod municipalities;
+mod projection;
+mod search;
 mod shapes;
+mod similarity;
+mod simplify;
+mod spectrum;
 #[cfg(test)]
 mod test;
 
-use graph::model::{model, update};
+use graph::model::{model, prepare_shape, update};
 
-fn main() {
-    nannou::app(model).update(update).run();
+fn main() -> anyhow::Result<()> {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() {
+        prepare_shape()?;
+        nannou::app(model).update(update).run();
+        Ok(())
+    } else {
+        commands::run(&args)
+    }
 }
 
 /// `create_shape`関数で作られた形にFFTを適用して結果をファイルに出力する。
 /// `gnuplot`でプロットして確かめられる。
 #[test]
 fn test_basic_shape_fft() {
-    use fft::{create_shape, fft_points};
+    use fft::{create_shape, fft_points, FftNormalization};
     use io::output_2d_sequences;
 
-    let shape_points = create_shape();
-    let fft_result = fft_points(&shape_points);
+    let shape_points = create_shape().unwrap();
+    let fft_result = fft_points(&shape_points, FftNormalization::None);
     output_2d_sequences(
         "shape.dat",
         &shape_points
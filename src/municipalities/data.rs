@@ -73,3 +73,65 @@ pub const ORDINANCE_DISIGNATED_CITIES: [&str; 20] = [
     "福岡市",
     "熊本市",
 ];
+
+/// 地方区分（八地方区分）と属する都道府県
+pub const REGIONS: [(&str, &[&str]); 8] = [
+    ("北海道", &["北海道"]),
+    (
+        "東北",
+        &["青森県", "岩手県", "宮城県", "秋田県", "山形県", "福島県"],
+    ),
+    (
+        "関東",
+        &[
+            "茨城県",
+            "栃木県",
+            "群馬県",
+            "埼玉県",
+            "千葉県",
+            "東京都",
+            "神奈川県",
+        ],
+    ),
+    (
+        "中部",
+        &[
+            "新潟県",
+            "富山県",
+            "石川県",
+            "福井県",
+            "山梨県",
+            "長野県",
+            "岐阜県",
+            "静岡県",
+            "愛知県",
+        ],
+    ),
+    (
+        "近畿",
+        &[
+            "三重県",
+            "滋賀県",
+            "京都府",
+            "大阪府",
+            "兵庫県",
+            "奈良県",
+            "和歌山県",
+        ],
+    ),
+    ("中国", &["鳥取県", "島根県", "岡山県", "広島県", "山口県"]),
+    ("四国", &["徳島県", "香川県", "愛媛県", "高知県"]),
+    (
+        "九州",
+        &[
+            "福岡県",
+            "佐賀県",
+            "長崎県",
+            "熊本県",
+            "大分県",
+            "宮崎県",
+            "鹿児島県",
+            "沖縄県",
+        ],
+    ),
+];
@@ -0,0 +1,195 @@
+//! 国土数値情報（行政区域）のデータの置き場所と版を表す
+
+use std::{
+    env,
+    fmt::Display,
+    fs::read_dir,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::OnceLock,
+};
+
+use regex::Regex;
+
+use super::data::PREFECTURES;
+use crate::error::{Error, Result};
+
+/// データディレクトリを指定する環境変数
+pub const DATA_DIR_ENV: &str = "EPICYCLE_DATA_DIR";
+/// データの版を指定する環境変数（指定しなければファイル名から最新の版を探す）
+pub const VINTAGE_ENV: &str = "EPICYCLE_DATA_VINTAGE";
+
+/// 自治体名の読みの表のファイル名（データディレクトリに置く）
+pub const READINGS_FILENAME: &str = "municipality_readings.csv";
+
+/// データファイル名`N03-YY_PP_YYMMDD.geojson`あるいは`N03-YY_PP_YYMMDD.shp`の形
+const FILENAME_PATTERN: &str = r"^N03-(\d{2})_(\d{2})_(\d{6})\.(geojson|shp)$";
+
+/// データの版。ファイル名`N03-YY_PP_YYMMDD.geojson`のうち、公開年`YY`と基準日`YYMMDD`。
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Vintage {
+    /// 公開年（西暦の下2桁）
+    pub year: String,
+    /// 基準日（YYMMDD）
+    pub date: String,
+}
+
+impl Vintage {
+    /// ファイル名から版を取り出す。形が合わなければ`None`を返す。
+    fn from_filename(filename: &str) -> Option<Self> {
+        static RE: OnceLock<Regex> = OnceLock::new();
+        let re = RE.get_or_init(|| Regex::new(FILENAME_PATTERN).unwrap());
+        let captures = re.captures(filename)?;
+        Some(Self {
+            year: captures[1].to_string(),
+            date: captures[3].to_string(),
+        })
+    }
+}
+
+/// `YY_YYMMDD`あるいは`YYMMDD`（公開年は基準日の年とする）の形の文字列から版を作る。
+impl FromStr for Vintage {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (year, date) = s.split_once('_').unwrap_or((s.get(..2).unwrap_or(s), s));
+        let is_digits =
+            |v: &str, len: usize| v.len() == len && v.bytes().all(|b| b.is_ascii_digit());
+        if !is_digits(year, 2) || !is_digits(date, 6) {
+            return Err(Error::InvalidOption {
+                kind: "dataset vintage",
+                value: s.to_string(),
+            });
+        }
+        Ok(Self {
+            year: year.to_string(),
+            date: date.to_string(),
+        })
+    }
+}
+
+impl Display for Vintage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}_{}", self.year, self.date)
+    }
+}
+
+/// データディレクトリと版の組。境界データのファイルはこれを通して探す。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dataset {
+    pub root: PathBuf,
+    pub vintage: Vintage,
+}
+
+impl Dataset {
+    /// データディレクトリと版を指定する。
+    pub fn new(root: impl Into<PathBuf>, vintage: Vintage) -> Self {
+        Self {
+            root: root.into(),
+            vintage,
+        }
+    }
+
+    /// データディレクトリ内のファイル名から最新の版を探す。
+    pub fn discover(root: impl Into<PathBuf>) -> Result<Self> {
+        let root = root.into();
+        let vintage = Self::vintages(&root)?
+            .pop()
+            .ok_or_else(|| Error::DatasetNotFound {
+                root: root.display().to_string(),
+            })?;
+        Ok(Self { root, vintage })
+    }
+
+    /// 環境変数`EPICYCLE_DATA_DIR`（無ければカレントディレクトリ）をデータディレクトリとする。
+    /// 版は`EPICYCLE_DATA_VINTAGE`で指定されていればそれを使い、無ければファイル名から探す。
+    pub fn from_env() -> Result<Self> {
+        let root = env::var_os(DATA_DIR_ENV).map_or_else(|| PathBuf::from("."), PathBuf::from);
+        match env::var(VINTAGE_ENV) {
+            Ok(vintage) => Ok(Self::new(root, vintage.parse()?)),
+            Err(_) => Self::discover(root),
+        }
+    }
+
+    /// データディレクトリ内にファイルがある版を古い順に返す。
+    pub fn vintages(root: &Path) -> Result<Vec<Vintage>> {
+        let entries = read_dir(root).map_err(Error::io(&root.display().to_string()))?;
+        let mut vintages = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| Vintage::from_filename(entry.file_name().to_str()?))
+            .collect::<Vec<_>>();
+        vintages.sort();
+        vintages.dedup();
+        Ok(vintages)
+    }
+
+    /// 自治体名の読みの表のファイルのパスを返す。読みは版によらないのでデータディレクトリ直下に一つだけ置く。
+    pub fn readings_path(&self) -> PathBuf {
+        self.root.join(READINGS_FILENAME)
+    }
+
+    /// 都道府県名から対応する境界データのファイルのパスを返す。
+    /// GeoJSONが無くShapefile（.shp）がある場合はそちらのパスを返す。どちらも無ければGeoJSONのパスを返す。
+    pub fn boundary_data_path(&self, prefecture_name: &str) -> Result<PathBuf> {
+        let id = PREFECTURES
+            .iter()
+            .position(|&pref| pref == prefecture_name)
+            .filter(|&id| id > 0)
+            .ok_or_else(|| Error::UnknownPrefecture(prefecture_name.to_string()))?;
+        let geojson = self.root.join(format!(
+            "N03-{}_{:02}_{}.geojson",
+            self.vintage.year, id, self.vintage.date
+        ));
+        let shp = geojson.with_extension("shp");
+        if !geojson.exists() && shp.exists() {
+            return Ok(shp);
+        }
+        Ok(geojson)
+    }
+}
+
+/// ファイル名から版を読み取り、最新の版のファイルのパスを組み立てられることを確かめる。
+#[test]
+fn test_dataset_discovery() {
+    use std::fs::write;
+
+    use crate::test::TempDir;
+
+    let dir = TempDir::new("dataset_discovery");
+    let root = dir.path();
+    for name in [
+        "N03-22_28_220101.geojson",
+        "N03-23_28_230101.geojson",
+        "N03-23_01_230101.geojson",
+        "N03-23_13_230101.shp",
+        "N03-24_28_240101.json",
+        "README.txt",
+    ] {
+        write(root.join(name), "").unwrap();
+    }
+
+    let vintages = Dataset::vintages(root).unwrap();
+    assert_eq!(
+        vintages,
+        vec!["22_220101".parse().unwrap(), "230101".parse().unwrap()]
+    );
+    let dataset = Dataset::discover(root).unwrap();
+    assert_eq!(dataset.vintage.to_string(), "23_230101");
+    assert_eq!(
+        dataset.boundary_data_path("北海道").unwrap(),
+        root.join("N03-23_01_230101.geojson")
+    );
+    assert_eq!(
+        Dataset::new(root, vintages[0].clone())
+            .boundary_data_path("兵庫県")
+            .unwrap(),
+        root.join("N03-22_28_220101.geojson")
+    );
+    assert_eq!(
+        dataset.boundary_data_path("東京都").unwrap(),
+        root.join("N03-23_13_230101.shp")
+    );
+    assert!(dataset.boundary_data_path("架空県").is_err());
+    assert!("2301".parse::<Vintage>().is_err());
+    assert!(Dataset::discover(root.join("missing")).is_err());
+}
@@ -0,0 +1,98 @@
+//! 行政区域コードと自治体名を相互に引くための索引
+
+use std::collections::HashMap;
+
+use super::{
+    data::PREFECTURES, dataset::Dataset, mapping::PropertyMapping, readings::ReadingTable,
+    serde_models::GeoFeature,
+};
+use crate::{error::Result, io::read_municipalities_boundary_data};
+
+/// 一つの自治体の行政区域コードと名前
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GazetteerEntry {
+    pub code: String,
+    /// GISデータ内の完全名（例: "兵庫県丹波篠山市"）
+    pub name: String,
+    pub prefecture: String,
+    /// 市区町村名（N03_004、例: "丹波篠山市"）
+    pub municipality: String,
+    /// 読み（ひらがな）。読みの表を与えるまでは`None`
+    pub reading: Option<String>,
+}
+
+/// メモリ上に保持した行政区域コードと自治体名の対応表
+#[derive(Debug, Default)]
+pub struct Gazetteer {
+    entries: Vec<GazetteerEntry>,
+    by_code: HashMap<String, usize>,
+    by_name: HashMap<String, usize>,
+}
+
+impl Gazetteer {
+    /// `dataset`の全都道府県のファイルを読み込んで対応表を作る。
+    pub fn load_all(dataset: &Dataset) -> Result<Self> {
+        let mut gazetteer = Self::default();
+        for prefecture_name in PREFECTURES.iter().skip(1) {
+            let json_data = read_municipalities_boundary_data(dataset, prefecture_name)?;
+            gazetteer.add_features(prefecture_name, &json_data.features)?;
+        }
+        Ok(gazetteer)
+    }
+
+    /// Featureの列から自治体を登録する。行政区域コードを持たないもの（所属未定地など）は無視する。
+    /// 同じコードの自治体が既にあれば登録しない。
+    pub fn add_features(&mut self, prefecture_name: &str, features: &[GeoFeature]) -> Result<()> {
+        let mapping = PropertyMapping::n03();
+        for feat in features.iter() {
+            let Some(code) = mapping.id(&feat.properties) else {
+                continue;
+            };
+            if self.by_code.contains_key(&code) {
+                continue;
+            }
+            let name = mapping.name(&feat.properties);
+            let idx = self.entries.len();
+            self.by_code.insert(code.clone(), idx);
+            self.by_name.insert(name.clone(), idx);
+            let municipality = mapping.local_name(&feat.properties).unwrap_or_default();
+            self.entries.push(GazetteerEntry {
+                code,
+                name,
+                prefecture: prefecture_name.to_string(),
+                municipality,
+                reading: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// 読みの表から各自治体の読みを行政区域コード、無ければ都道府県名と市区町村名で引いて設定する。
+    /// 表に無かった自治体を登録順に返す。
+    pub fn set_readings(&mut self, table: &ReadingTable) -> Vec<&GazetteerEntry> {
+        for entry in self.entries.iter_mut() {
+            entry.reading = table
+                .get(&entry.code, &entry.prefecture, &entry.municipality)
+                .map(str::to_string);
+        }
+        self.entries
+            .iter()
+            .filter(|entry| entry.reading.is_none())
+            .collect()
+    }
+
+    /// 登録された全ての自治体を登録順に返す。
+    pub fn entries(&self) -> &[GazetteerEntry] {
+        &self.entries
+    }
+
+    /// 行政区域コードから自治体を引く。
+    pub fn find_by_code(&self, code: &str) -> Option<&GazetteerEntry> {
+        self.by_code.get(code).map(|&idx| &self.entries[idx])
+    }
+
+    /// 完全名から自治体を引く。
+    pub fn find_by_name(&self, name: &str) -> Option<&GazetteerEntry> {
+        self.by_name.get(name).map(|&idx| &self.entries[idx])
+    }
+}
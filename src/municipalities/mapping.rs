@@ -0,0 +1,155 @@
+//! 任意のGeoJSONのプロパティから表示名と一意なIDを作る対応付け
+
+use std::{collections::HashMap, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use super::data::ORDINANCE_DISIGNATED_CITIES;
+use crate::error::{Error, Result};
+
+/// Featureのプロパティのうち、どれを表示名とIDとして使うかの対応付け
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PropertyMapping {
+    /// 表示名を構成するプロパティのキー。値のあるものをこの順に`separator`でつなぐ
+    pub name_keys: Vec<String>,
+    pub separator: String,
+    /// キーごとに、表示名に含めない値（N03の`N03_003`の政令指定都市名など）
+    #[serde(default)]
+    pub omitted_values: HashMap<String, Vec<String>>,
+    /// 一意なIDとなるプロパティのキー。`None`の場合はIDを持たない
+    pub id_key: Option<String>,
+}
+
+impl PropertyMapping {
+    /// 国土数値情報の行政区域データ（N03）の対応付け。
+    /// 表示名は都道府県名・振興局名・郡名・市区町村名をつないだもの（例: "兵庫県丹波篠山市"）とし、
+    /// 行政区域コード（`N03_007`）をIDとする。
+    pub fn n03() -> Self {
+        // N03_001: 都道府県名
+        // N03_002: 支庁・振興局名（北海道のみ）
+        // N03_003: 郡・政令都市名
+        // N03_004: 市区町村名
+        // 5, 6は欠番
+        // N03_007: 行政区域コード
+        Self {
+            name_keys: ["N03_001", "N03_002", "N03_003", "N03_004"]
+                .map(String::from)
+                .to_vec(),
+            separator: String::new(),
+            // 3番目が政令指定都市の場合4番目に含まれているので除外
+            omitted_values: HashMap::from([(
+                "N03_003".to_string(),
+                ORDINANCE_DISIGNATED_CITIES.map(String::from).to_vec(),
+            )]),
+            id_key: Some("N03_007".to_string()),
+        }
+    }
+
+    /// プロパティから表示名を作る。名前となる値が一つも無い場合は空文字列を返す。
+    pub fn name(&self, props: &HashMap<String, Option<String>>) -> String {
+        self.name_keys
+            .iter()
+            .filter_map(|key| {
+                let value = props.get(key)?.as_deref()?;
+                let omitted = self
+                    .omitted_values
+                    .get(key)
+                    .is_some_and(|values| values.iter().any(|v| v == value));
+                (!omitted).then_some(value)
+            })
+            .collect::<Vec<_>>()
+            .join(&self.separator)
+    }
+
+    /// 表示名を構成する値のうち最後のもの（N03では市区町村名）を返す。値が一つも無い場合は`None`を返す。
+    pub fn local_name(&self, props: &HashMap<String, Option<String>>) -> Option<String> {
+        self.name_keys
+            .iter()
+            .rev()
+            .find_map(|key| props.get(key).cloned().flatten())
+    }
+
+    /// プロパティからIDを返す。IDのキーが無いか値が無い場合は`None`を返す。
+    pub fn id(&self, props: &HashMap<String, Option<String>>) -> Option<String> {
+        props.get(self.id_key.as_ref()?).cloned().flatten()
+    }
+}
+
+/// `n03`、あるいは`<名前のキー>[+<名前のキー>...][:<IDのキー>]`の形の文字列から対応付けを作る。
+/// 後者の場合、名前の値は空白でつなぐ。
+impl FromStr for PropertyMapping {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "n03" {
+            return Ok(Self::n03());
+        }
+        let (names, id_key) = match s.split_once(':') {
+            Some((names, id_key)) => (names, Some(id_key)),
+            None => (s, None),
+        };
+        let name_keys = names.split('+').map(String::from).collect::<Vec<_>>();
+        if name_keys.iter().any(|key| key.is_empty()) || id_key == Some("") {
+            return Err(Error::InvalidOption {
+                kind: "property mapping",
+                value: s.to_string(),
+            });
+        }
+        Ok(Self {
+            name_keys,
+            separator: " ".to_string(),
+            omitted_values: HashMap::new(),
+            id_key: id_key.map(String::from),
+        })
+    }
+}
+
+/// N03の対応付けと、文字列から作った任意の対応付けで名前とIDが取り出せることを確かめる。
+#[test]
+fn test_property_mapping() {
+    let props = |pairs: &[(&str, Option<&str>)]| {
+        pairs
+            .iter()
+            .map(|&(k, v)| (k.to_string(), v.map(String::from)))
+            .collect::<HashMap<_, _>>()
+    };
+
+    let n03 = PropertyMapping::n03();
+    let ward = props(&[
+        ("N03_001", Some("兵庫県")),
+        ("N03_002", None),
+        ("N03_003", Some("神戸市")),
+        ("N03_004", Some("神戸市中央区")),
+        ("N03_007", Some("28110")),
+    ]);
+    assert_eq!(n03.name(&ward), "兵庫県神戸市中央区");
+    assert_eq!(n03.id(&ward).as_deref(), Some("28110"));
+    assert_eq!(n03.local_name(&ward).as_deref(), Some("神戸市中央区"));
+    let town = props(&[
+        ("N03_001", Some("京都府")),
+        ("N03_003", Some("与謝郡")),
+        ("N03_004", Some("伊根町")),
+        ("N03_007", None),
+    ]);
+    assert_eq!(n03.name(&town), "京都府与謝郡伊根町");
+    assert_eq!(n03.id(&town), None);
+    assert_eq!("n03".parse::<PropertyMapping>().unwrap(), n03);
+
+    let mapping = "NAME_1+NAME_2:GID_2".parse::<PropertyMapping>().unwrap();
+    let district = props(&[
+        ("NAME_1", Some("Bayern")),
+        ("NAME_2", Some("München")),
+        ("GID_2", Some("DEU.2.61_1")),
+        ("TYPE_2", Some("Landkreis")),
+    ]);
+    assert_eq!(mapping.name(&district), "Bayern München");
+    assert_eq!(mapping.id(&district).as_deref(), Some("DEU.2.61_1"));
+    assert_eq!(mapping.local_name(&district).as_deref(), Some("München"));
+    let lake = "name".parse::<PropertyMapping>().unwrap();
+    assert_eq!(lake.name(&props(&[("name", Some("琵琶湖"))])), "琵琶湖");
+    assert_eq!(lake.id(&props(&[("name", Some("琵琶湖"))])), None);
+
+    for invalid in ["", "a++b", "name:"] {
+        assert!(invalid.parse::<PropertyMapping>().is_err(), "{}", invalid);
+    }
+}
@@ -1,3 +1,11 @@
 pub mod data;
+pub mod dataset;
+pub mod gazetteer;
+pub mod mapping;
+pub mod name_search;
+pub mod readings;
 pub mod serde_models;
+pub mod shapefile;
+pub mod topojson;
 pub mod utils;
+pub mod validation;
@@ -0,0 +1,269 @@
+//! 部分的な名前や読み、ローマ字から自治体を探す
+
+use super::{
+    data::PREFECTURES,
+    gazetteer::{Gazetteer, GazetteerEntry},
+    readings::{normalize_romaji, romanize, to_hiragana},
+};
+use crate::error::{Error, Result};
+
+/// 市区町村名の末尾の種別
+const NAME_SUFFIXES: [&str; 4] = ["市", "区", "町", "村"];
+/// 読みの末尾の種別
+const READING_SUFFIXES: [&str; 6] = ["し", "く", "ちょう", "まち", "そん", "むら"];
+
+/// どのように一致したか
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchKind {
+    /// 都道府県名を含む完全名が一致した
+    FullName,
+    /// 市区町村名が一致した（"市"や"町"の有無は問わない）
+    Name,
+    /// 読みが一致した
+    Reading,
+    /// ローマ字が一致した
+    Romaji,
+    /// 名前・読み・ローマ字の一部が一致した
+    Partial,
+}
+
+/// 検索で見つかった候補
+#[derive(Debug, Clone)]
+pub struct NameMatch<'a> {
+    pub entry: &'a GazetteerEntry,
+    pub kind: MatchKind,
+    /// 一致の度合い（大きいほど良い）
+    pub score: f64,
+}
+
+/// 検索の結果。候補は一致の度合いの降順に並ぶ。
+#[derive(Debug, Clone)]
+pub struct NameSearch<'a> {
+    pub query: String,
+    pub matches: Vec<NameMatch<'a>>,
+}
+
+impl<'a> NameSearch<'a> {
+    /// 最も良く一致した候補が複数ある場合はそれらを返す。一つに決まる場合は空の列を返す。
+    pub fn ambiguous(&self) -> Vec<&'a GazetteerEntry> {
+        let Some(best) = self.matches.first() else {
+            return vec![];
+        };
+        let tied = self
+            .matches
+            .iter()
+            .take_while(|m| m.score == best.score)
+            .map(|m| m.entry)
+            .collect::<Vec<_>>();
+        if tied.len() > 1 {
+            tied
+        } else {
+            vec![]
+        }
+    }
+
+    /// 最も良く一致した候補を返す。候補が無い場合や一つに決まらない場合はエラーとする。
+    pub fn best(&self) -> Result<&'a GazetteerEntry> {
+        let ambiguous = self.ambiguous();
+        if !ambiguous.is_empty() {
+            return Err(Error::AmbiguousName {
+                query: self.query.clone(),
+                candidates: ambiguous.iter().map(|e| e.name.clone()).collect(),
+            });
+        }
+        self.matches
+            .first()
+            .map(|m| m.entry)
+            .ok_or_else(|| Error::MunicipalityNotFound {
+                name: self.query.clone(),
+                location: "all prefectures".to_string(),
+            })
+    }
+}
+
+/// 末尾の種別を取り除く。
+fn strip_suffix<'s>(s: &'s str, suffixes: &[&str]) -> &'s str {
+    suffixes
+        .iter()
+        .find_map(|suffix| s.strip_suffix(suffix).filter(|rest| !rest.is_empty()))
+        .unwrap_or(s)
+}
+
+/// 文字列が全てひらがな（長音記号を含む）かどうかを返す。
+fn is_hiragana(s: &str) -> bool {
+    s.chars().all(|c| matches!(c, 'ぁ'..='ゖ' | 'ー'))
+}
+
+/// 一つの候補について一致の種類と度合いを返す。一致しなければ`None`を返す。
+fn score_entry(entry: &GazetteerEntry, query: &str) -> Option<(MatchKind, f64)> {
+    if entry.name == query {
+        return Some((MatchKind::FullName, 1.0));
+    }
+    let name = entry.municipality.as_str();
+    if name == query || strip_suffix(name, &NAME_SUFFIXES) == query {
+        return Some((MatchKind::Name, 0.9));
+    }
+    let partial = |query: &str, candidate: &str| {
+        let ratio = query.chars().count() as f64 / candidate.chars().count().max(1) as f64;
+        candidate
+            .contains(query)
+            .then_some((MatchKind::Partial, 0.5 * ratio.min(1.0)))
+    };
+
+    let reading = entry.reading.as_deref();
+    let kana = to_hiragana(query);
+    if is_hiragana(&kana) {
+        let reading = reading?;
+        if reading == kana || strip_suffix(reading, &READING_SUFFIXES) == kana {
+            return Some((MatchKind::Reading, 0.8));
+        }
+        return partial(&kana, reading);
+    }
+    if query.is_ascii() {
+        let reading = reading?;
+        let romaji = normalize_romaji(&romanize(reading));
+        let stem = normalize_romaji(&romanize(strip_suffix(reading, &READING_SUFFIXES)));
+        let query = normalize_romaji(query);
+        if query.is_empty() {
+            return None;
+        }
+        if romaji == query || stem == query {
+            return Some((MatchKind::Romaji, 0.8));
+        }
+        return partial(&query, &romaji);
+    }
+    partial(query, name)
+}
+
+/// 全国の自治体から`query`に一致するものを探す。
+/// `query`は完全名・市区町村名（"市"などは省略可）・その一部・読み（ひらがなかカタカナ）・ローマ字のいずれでもよい。
+/// 都道府県名から始まる場合は、その都道府県内に絞って残りの部分で探す。
+pub fn search_names<'a>(gazetteer: &'a Gazetteer, query: &str) -> NameSearch<'a> {
+    let query = query.trim();
+    let (prefecture, rest) = PREFECTURES
+        .iter()
+        .skip(1)
+        .find_map(|&pref| Some((Some(pref), query.strip_prefix(pref)?)))
+        .filter(|(_, rest)| !rest.is_empty())
+        .unwrap_or((None, query));
+    let mut matches = gazetteer
+        .entries()
+        .iter()
+        .filter(|entry| prefecture.is_none_or(|pref| entry.prefecture == pref))
+        .filter_map(|entry| {
+            let (kind, score) = score_entry(entry, query)
+                .or_else(|| score_entry(entry, rest).filter(|_| prefecture.is_some()))?;
+            Some(NameMatch { entry, kind, score })
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.entry.code.cmp(&b.entry.code))
+    });
+    NameSearch {
+        query: query.to_string(),
+        matches,
+    }
+}
+
+/// 名前の一部・読み・ローマ字で探せること、同名の自治体を曖昧として報告することを確かめる。
+#[test]
+fn test_search_names() {
+    use std::collections::HashMap;
+
+    use super::{
+        readings::ReadingTable,
+        serde_models::{FeatureGeometry, GeoFeature},
+    };
+
+    let feature = |pref: &str, county: Option<&str>, name: &str, code: &str| GeoFeature {
+        _type: "Feature".to_string(),
+        properties: HashMap::from([
+            ("N03_001".to_string(), Some(pref.to_string())),
+            ("N03_002".to_string(), None),
+            ("N03_003".to_string(), county.map(|c| c.to_string())),
+            ("N03_004".to_string(), Some(name.to_string())),
+            ("N03_007".to_string(), Some(code.to_string())),
+        ]),
+        geometry: FeatureGeometry::Polygon {
+            coordinates: vec![],
+        },
+    };
+    let mut gazetteer = Gazetteer::default();
+    gazetteer
+        .add_features(
+            "兵庫県",
+            &[
+                feature("兵庫県", None, "丹波篠山市", "28221"),
+                feature("兵庫県", None, "丹波市", "28223"),
+                feature("兵庫県", None, "姫路市", "28201"),
+            ],
+        )
+        .unwrap();
+    gazetteer
+        .add_features("東京都", &[feature("東京都", None, "府中市", "13206")])
+        .unwrap();
+    gazetteer
+        .add_features(
+            "広島県",
+            &[
+                feature("広島県", None, "府中市", "34208"),
+                feature("広島県", Some("安芸郡"), "府中町", "34302"),
+            ],
+        )
+        .unwrap();
+    // 読みが無くても漢字の名前では探せる
+    assert_eq!(
+        search_names(&gazetteer, "丹波篠山").best().unwrap().code,
+        "28221"
+    );
+    assert!(search_names(&gazetteer, "たんばささやま")
+        .matches
+        .is_empty());
+    assert!(gazetteer.set_readings(&ReadingTable::builtin()).is_empty());
+
+    for query in [
+        "兵庫県丹波篠山市",
+        "丹波篠山",
+        "篠山市",
+        "たんばささやま",
+        "タンバササヤマシ",
+        "Tamba-Sasayama",
+        "tanbasasayama shi",
+        "兵庫県篠山",
+    ] {
+        let search = search_names(&gazetteer, query);
+        assert_eq!(search.best().unwrap().code, "28221", "{}", query);
+    }
+    assert_eq!(
+        search_names(&gazetteer, "丹波篠山").matches[0].kind,
+        MatchKind::Name
+    );
+    // "丹波"は丹波市と完全に一致し、丹波篠山市とは部分的に一致する
+    let search = search_names(&gazetteer, "丹波");
+    assert_eq!(search.best().unwrap().code, "28223");
+    assert_eq!(search.matches[1].kind, MatchKind::Partial);
+
+    // 同名の府中市は曖昧になるが、都道府県名を付ければ決まる
+    let search = search_names(&gazetteer, "府中市");
+    let ambiguous = search.ambiguous();
+    assert_eq!(ambiguous.len(), 2);
+    assert!(matches!(search.best(), Err(Error::AmbiguousName { .. })));
+    assert_eq!(
+        search_names(&gazetteer, "広島県府中市")
+            .best()
+            .unwrap()
+            .code,
+        "34208"
+    );
+    assert_eq!(
+        search_names(&gazetteer, "ふちゅうちょう")
+            .best()
+            .unwrap()
+            .code,
+        "34302"
+    );
+    assert!(search_names(&gazetteer, "大阪").matches.is_empty());
+    assert!(search_names(&gazetteer, "大阪").best().is_err());
+}
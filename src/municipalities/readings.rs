@@ -0,0 +1,432 @@
+//! 自治体名の読みと、かな・ローマ字の変換
+
+use std::{collections::HashMap, fs::read_to_string, path::Path};
+
+use super::dataset::Dataset;
+use crate::error::{Error, Result};
+
+/// 組み込みの読みの表。全国の市区町村と政令指定都市の区、北方領土の村の読みを、
+/// 総務省の「全国地方公共団体コード」と同じ列の並びで持つ（団体コードは空欄）。
+const BUILTIN_READINGS: &str = include_str!("readings.csv");
+
+/// 行政区域コードや自治体名から、自治体名の読み（ひらがな）を引く表。
+/// 総務省の「全国地方公共団体コード」をCSVにしたもの（団体コード、都道府県名（漢字）、
+/// 市区町村名（漢字）、都道府県名（カナ）、市区町村名（カナ）の順の列）から作る。
+/// 政令指定都市の区の読みは別の表にあるので、同じ形で後ろに付け加えておく。
+#[derive(Debug, Default)]
+pub struct ReadingTable {
+    by_code: HashMap<String, String>,
+    /// 都道府県名と市区町村名をつないだ名前（例: "兵庫県神戸市東灘区"）から読みを引く
+    by_name: HashMap<String, String>,
+}
+
+impl ReadingTable {
+    /// 実行ファイルに組み込んだ読みの表を返す。
+    pub fn builtin() -> Self {
+        Self::parse(BUILTIN_READINGS, "readings.csv").expect("built-in readings table is valid")
+    }
+
+    /// 組み込みの表に、データディレクトリの読みの表（あれば）を上書きしたものを返す。
+    /// 新しい版で増えた自治体や読みの変わった自治体は、データディレクトリの表で補える。
+    pub fn for_dataset(dataset: &Dataset) -> Result<Self> {
+        let mut table = Self::builtin();
+        let path = dataset.readings_path();
+        if path.is_file() {
+            table.extend(Self::load(&path)?);
+        }
+        Ok(table)
+    }
+
+    /// ファイルから読みの表を読み込む。
+    pub fn load(path: &Path) -> Result<Self> {
+        let path_str = path.display().to_string();
+        let text = read_to_string(path).map_err(Error::io(&path_str))?;
+        Self::parse(&text, &path_str)
+    }
+
+    /// CSVの文字列から読みの表を作る。団体コード以外で始まる行（見出し）と、市区町村名の無い行（都道府県）は飛ばす。
+    /// 団体コードは検査数字を含む6桁でも含まない5桁でもよく、空欄なら名前だけで引けるようにする。
+    /// カナは半角でも全角でも、ひらがなでもよい。
+    pub fn parse(text: &str, path: &str) -> Result<Self> {
+        let mut table = Self::default();
+        for (line_idx, line) in text.lines().enumerate() {
+            let invalid = |reason: &str| Error::InvalidReadings {
+                path: path.to_string(),
+                line: line_idx + 1,
+                reason: reason.to_string(),
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let columns = line
+                .split(',')
+                .map(|column| column.trim().trim_matches('"'))
+                .collect::<Vec<_>>();
+            let code = columns[0].trim_start_matches('\u{feff}');
+            if !code.is_empty() && !code.bytes().next().is_some_and(|b| b.is_ascii_digit()) {
+                continue;
+            }
+            if !code.is_empty()
+                && (!(code.len() == 5 || code.len() == 6)
+                    || !code.bytes().all(|b| b.is_ascii_digit()))
+            {
+                return Err(invalid("municipality code must be 5 or 6 digits"));
+            }
+            let [_, prefecture, name, _, kana] = columns[..] else {
+                return Err(invalid("expected 5 columns"));
+            };
+            if name.is_empty() {
+                continue;
+            }
+            let reading = to_hiragana(kana);
+            if reading.is_empty() {
+                return Err(invalid("empty reading"));
+            }
+            if !code.is_empty() {
+                table.by_code.insert(code[..5].to_string(), reading.clone());
+            }
+            table
+                .by_name
+                .insert(format!("{}{}", prefecture, name), reading);
+        }
+        Ok(table)
+    }
+
+    /// `other`の読みを加える。同じコードや名前の読みは`other`のもので置き換える。
+    pub fn extend(&mut self, other: Self) {
+        self.by_code.extend(other.by_code);
+        self.by_name.extend(other.by_name);
+    }
+
+    /// 行政区域コード（5桁）から読みを引き、無ければ都道府県名と市区町村名（N03_004）から引く。
+    pub fn get(&self, code: &str, prefecture: &str, municipality: &str) -> Option<&str> {
+        self.by_code
+            .get(code)
+            .or_else(|| self.by_name.get(&format!("{}{}", prefecture, municipality)))
+            .map(String::as_str)
+    }
+}
+
+/// 半角カタカナ（濁点・半濁点を含む）を全角にしたうえで、カタカナをひらがなにする。それ以外の文字はそのまま返す。
+pub fn to_hiragana(s: &str) -> String {
+    const HALFWIDTH: &str = "ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝ";
+    const FULLWIDTH: &str = "ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン";
+    let mut katakana = String::new();
+    for c in s.chars() {
+        let prev = katakana.chars().last();
+        match (c, prev) {
+            ('ﾞ', Some('ウ')) => {
+                katakana.pop();
+                katakana.push('ヴ');
+            }
+            ('ﾞ', Some(p @ ('カ'..='ト' | 'ハ'..='ホ'))) => {
+                katakana.pop();
+                katakana.push(char::from_u32(p as u32 + 1).unwrap());
+            }
+            ('ﾟ', Some(p @ 'ハ'..='ホ')) => {
+                katakana.pop();
+                katakana.push(char::from_u32(p as u32 + 2).unwrap());
+            }
+            _ => katakana.push(
+                HALFWIDTH
+                    .chars()
+                    .position(|h| h == c)
+                    .and_then(|idx| FULLWIDTH.chars().nth(idx))
+                    .unwrap_or(c),
+            ),
+        }
+    }
+    katakana
+        .chars()
+        .map(|c| match c {
+            'ァ'..='ヶ' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+            _ => c,
+        })
+        .collect()
+}
+
+/// ひらがな一文字のヘボン式ローマ字
+fn kana_romaji(c: char) -> Option<&'static str> {
+    const TABLE: [(char, &str); 83] = [
+        ('あ', "a"),
+        ('い', "i"),
+        ('う', "u"),
+        ('え', "e"),
+        ('お', "o"),
+        ('か', "ka"),
+        ('き', "ki"),
+        ('く', "ku"),
+        ('け', "ke"),
+        ('こ', "ko"),
+        ('さ', "sa"),
+        ('し', "shi"),
+        ('す', "su"),
+        ('せ', "se"),
+        ('そ', "so"),
+        ('た', "ta"),
+        ('ち', "chi"),
+        ('つ', "tsu"),
+        ('て', "te"),
+        ('と', "to"),
+        ('な', "na"),
+        ('に', "ni"),
+        ('ぬ', "nu"),
+        ('ね', "ne"),
+        ('の', "no"),
+        ('は', "ha"),
+        ('ひ', "hi"),
+        ('ふ', "fu"),
+        ('へ', "he"),
+        ('ほ', "ho"),
+        ('ま', "ma"),
+        ('み', "mi"),
+        ('む', "mu"),
+        ('め', "me"),
+        ('も', "mo"),
+        ('や', "ya"),
+        ('ゆ', "yu"),
+        ('よ', "yo"),
+        ('ら', "ra"),
+        ('り', "ri"),
+        ('る', "ru"),
+        ('れ', "re"),
+        ('ろ', "ro"),
+        ('わ', "wa"),
+        ('ゐ', "i"),
+        ('ゑ', "e"),
+        ('を', "o"),
+        ('ん', "n"),
+        ('が', "ga"),
+        ('ぎ', "gi"),
+        ('ぐ', "gu"),
+        ('げ', "ge"),
+        ('ご', "go"),
+        ('ざ', "za"),
+        ('じ', "ji"),
+        ('ず', "zu"),
+        ('ぜ', "ze"),
+        ('ぞ', "zo"),
+        ('だ', "da"),
+        ('ぢ', "ji"),
+        ('づ', "zu"),
+        ('で', "de"),
+        ('ど', "do"),
+        ('ば', "ba"),
+        ('び', "bi"),
+        ('ぶ', "bu"),
+        ('べ', "be"),
+        ('ぼ', "bo"),
+        ('ぱ', "pa"),
+        ('ぴ', "pi"),
+        ('ぷ', "pu"),
+        ('ぺ', "pe"),
+        ('ぽ', "po"),
+        ('ぁ', "a"),
+        ('ぃ', "i"),
+        ('ぅ', "u"),
+        ('ぇ', "e"),
+        ('ぉ', "o"),
+        ('ゃ', "ya"),
+        ('ゅ', "yu"),
+        ('ょ', "yo"),
+        ('ゔ', "vu"),
+        ('ー', ""),
+    ];
+    TABLE.iter().find(|(k, _)| *k == c).map(|(_, r)| *r)
+}
+
+/// ひらがな（カタカナも可）をヘボン式ローマ字にする。ローマ字にできない文字は捨てる。
+pub fn romanize(kana: &str) -> String {
+    let chars = to_hiragana(kana).chars().collect::<Vec<_>>();
+    let mut result = String::new();
+    let mut double_next = false;
+    let mut idx = 0;
+    while idx < chars.len() {
+        let c = chars[idx];
+        if c == 'っ' {
+            double_next = true;
+            idx += 1;
+            continue;
+        }
+        let Some(mut romaji) = kana_romaji(c).map(|r| r.to_string()) else {
+            idx += 1;
+            continue;
+        };
+        // 拗音（きゃ、しゅ、ちょなど）
+        if let Some(&small @ ('ゃ' | 'ゅ' | 'ょ')) = chars.get(idx + 1) {
+            if romaji.ends_with('i') && romaji.len() >= 2 {
+                let vowel = &kana_romaji(small).unwrap()[1..];
+                romaji.pop();
+                if !(romaji.ends_with("sh") || romaji.ends_with("ch") || romaji.ends_with('j')) {
+                    romaji.push('y');
+                }
+                romaji.push_str(vowel);
+                idx += 1;
+            }
+        }
+        if double_next {
+            match romaji.chars().next() {
+                Some('c') => result.push('t'),
+                Some(first) if !"aiueon".contains(first) => result.push(first),
+                _ => {}
+            }
+            double_next = false;
+        }
+        result.push_str(&romaji);
+        idx += 1;
+    }
+    result
+}
+
+/// ローマ字の表記の揺れを吸収した比較用の文字列にする。
+/// 訓令式とヘボン式の違い、撥音の`m`、長音の表記、大文字・小文字、記号を区別しない。
+pub fn normalize_romaji(s: &str) -> String {
+    let mut s = s
+        .to_lowercase()
+        .replace(['ā', 'â'], "a")
+        .replace(['ī', 'î'], "i")
+        .replace(['ū', 'û'], "u")
+        .replace(['ē', 'ê'], "e")
+        .replace(['ō', 'ô'], "o")
+        .chars()
+        .filter(|c| c.is_ascii_lowercase())
+        .collect::<String>();
+    for (from, to) in [
+        ("tch", "tty"),
+        ("cch", "tty"),
+        ("sh", "sy"),
+        ("ch", "ty"),
+        ("ts", "t"),
+        ("fu", "hu"),
+        ("j", "zy"),
+        ("syi", "si"),
+        ("tyi", "ti"),
+        ("zyi", "zi"),
+        ("mb", "nb"),
+        ("mp", "np"),
+        ("mm", "nm"),
+        ("ou", "o"),
+        ("oo", "o"),
+        ("uu", "u"),
+    ] {
+        s = s.replace(from, to);
+    }
+    s
+}
+
+/// ローマ字への変換と表記の揺れの吸収を確かめる。
+#[test]
+fn test_romanize() {
+    assert_eq!(romanize("たんばささやまし"), "tanbasasayamashi");
+    assert_eq!(romanize("ホッカイドウ"), "hokkaidou");
+    assert_eq!(romanize("きょうと"), "kyouto");
+    assert_eq!(romanize("じょうようし"), "jouyoushi");
+    assert_eq!(romanize("ふちゅうし"), "fuchuushi");
+    assert_eq!(romanize("さっぽろ"), "sapporo");
+    assert_eq!(romanize("まっちゃ"), "matcha");
+    assert_eq!(
+        normalize_romaji("Tamba-Sasayama"),
+        normalize_romaji(&romanize("たんばささやま"))
+    );
+    assert_eq!(normalize_romaji("Kōbe"), normalize_romaji("koube"));
+    assert_eq!(normalize_romaji("Hutyu"), normalize_romaji("fuchuu"));
+    // 促音と"ch"の組は訓令式の"tty"と同じになる
+    assert_eq!(normalize_romaji("matcha"), normalize_romaji("mattya"));
+    assert_eq!(normalize_romaji("maccha"), normalize_romaji("mattya"));
+    assert_ne!(normalize_romaji("matcha"), normalize_romaji("matta"));
+    assert_eq!(to_hiragana("ﾋﾒｼﾞｼ"), "ひめじし");
+    assert_eq!(to_hiragana("ｻｯﾎﾟﾛｼ"), "さっぽろし");
+}
+
+/// 読みの表のCSVから、見出しと都道府県の行を飛ばして自治体の読みを引けることを確かめる。
+#[test]
+fn test_reading_table() {
+    let csv = "\u{feff}団体コード,都道府県名（漢字）,市区町村名（漢字）,都道府県名（カナ）,市区町村名（カナ）
+280003,兵庫県,,ﾋｮｳｺﾞｹﾝ,
+282014,兵庫県,姫路市,ﾋｮｳｺﾞｹﾝ,ﾋﾒｼﾞｼ
+\"28221\",\"兵庫県\",\"丹波篠山市\",\"ヒョウゴケン\",\"タンバササヤマシ\"
+
+,兵庫県,神戸市東灘区,,ひがしなだく
+";
+    let table = ReadingTable::parse(csv, "readings.csv").unwrap();
+    assert_eq!(table.get("28201", "", ""), Some("ひめじし"));
+    assert_eq!(table.get("28221", "", ""), Some("たんばささやまし"));
+    assert_eq!(table.get("28000", "兵庫県", ""), None);
+    // 団体コードが空欄の行やコードの合わない行は名前で引く
+    assert_eq!(
+        table.get("28101", "兵庫県", "神戸市東灘区"),
+        Some("ひがしなだく")
+    );
+    assert_eq!(table.get("99999", "兵庫県", "姫路市"), Some("ひめじし"));
+    assert!(matches!(
+        ReadingTable::parse("2820,兵庫県,姫路市,ﾋｮｳｺﾞｹﾝ,ﾋﾒｼﾞｼ", "readings.csv"),
+        Err(Error::InvalidReadings { line: 1, .. })
+    ));
+    assert!(matches!(
+        ReadingTable::parse("282014,兵庫県,姫路市", "readings.csv"),
+        Err(Error::InvalidReadings { line: 1, .. })
+    ));
+
+    // 後から加えた表の読みが優先される
+    let mut builtin = ReadingTable::builtin();
+    assert_eq!(
+        builtin.get("28101", "兵庫県", "神戸市東灘区"),
+        Some("こうべしひがしなだく")
+    );
+    builtin.extend(table);
+    assert_eq!(
+        builtin.get("28101", "兵庫県", "神戸市東灘区"),
+        Some("ひがしなだく")
+    );
+}
+
+/// 組み込みの読みの表が読み込め、全ての読みがひらがなで、全国の市区町村と政令指定都市の区を含むことを確かめる。
+#[test]
+fn test_builtin_readings() {
+    use super::data::{ORDINANCE_DISIGNATED_CITIES, PREFECTURES};
+
+    let table = ReadingTable::builtin();
+    assert!(table.by_code.is_empty());
+    assert!(table
+        .by_name
+        .values()
+        .all(|reading| reading.chars().all(|c| matches!(c, 'ぁ'..='ゖ' | 'ー'))));
+    // 1,741市区町村と政令指定都市の171区、2024年の再編前の浜松市の6区、北方領土の村のうち後志の泊村と同名でない5村
+    assert_eq!(table.by_name.len(), 1741 + 171 + 6 + 5);
+    for prefecture in PREFECTURES.iter().skip(1) {
+        assert!(
+            table
+                .by_name
+                .keys()
+                .any(|name| name.starts_with(prefecture)),
+            "{}",
+            prefecture
+        );
+    }
+    for city in ORDINANCE_DISIGNATED_CITIES {
+        assert!(
+            table.by_name.keys().any(|name| name.contains(city)),
+            "{}",
+            city
+        );
+    }
+    for (prefecture, municipality, reading) in [
+        ("北海道", "札幌市中央区", "さっぽろしちゅうおうく"),
+        ("北海道", "蘂取村", "しべとろむら"),
+        ("東京都", "府中市", "ふちゅうし"),
+        ("広島県", "府中市", "ふちゅうし"),
+        ("広島県", "府中町", "ふちゅうちょう"),
+        ("京都府", "伊根町", "いねちょう"),
+        ("兵庫県", "丹波篠山市", "たんばささやまし"),
+        ("静岡県", "浜松市浜名区", "はままつしはまなく"),
+        ("沖縄県", "北谷町", "ちゃたんちょう"),
+    ] {
+        assert_eq!(
+            table.get("", prefecture, municipality),
+            Some(reading),
+            "{}{}",
+            prefecture,
+            municipality
+        );
+    }
+}
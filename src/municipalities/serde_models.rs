@@ -14,14 +14,84 @@ pub struct Crs {
     pub properties: CrsProp,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct FeatureGeometry {
-    #[serde(rename = "type")]
-    pub _type: String,
-    pub coordinates: Vec<Vec<Vec<f64>>>,
+/// 座標。`[経度, 緯度]`の順に並ぶ。
+pub type Position = Vec<f64>;
+/// 最初と最後の点が一致する閉じた座標列
+pub type LinearRing = Vec<Position>;
+
+/// `type`フィールドで種類が区別されるジオメトリ。
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type")]
+pub enum FeatureGeometry {
+    Point {
+        coordinates: Position,
+    },
+    MultiPoint {
+        coordinates: Vec<Position>,
+    },
+    LineString {
+        coordinates: Vec<Position>,
+    },
+    MultiLineString {
+        coordinates: Vec<Vec<Position>>,
+    },
+    /// 最初の環が外周、残りが穴（内周）
+    Polygon {
+        coordinates: Vec<LinearRing>,
+    },
+    MultiPolygon {
+        coordinates: Vec<Vec<LinearRing>>,
+    },
+    GeometryCollection {
+        geometries: Vec<FeatureGeometry>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// ポリゴン一つ分の外周と穴。
+#[derive(Debug, Clone, Copy)]
+pub struct PolygonRings<'a> {
+    pub exterior: &'a LinearRing,
+    pub interiors: &'a [LinearRing],
+}
+
+impl FeatureGeometry {
+    /// 含まれるポリゴンを全て取り出す。点や線は無視する。
+    pub fn polygons(&self) -> Vec<PolygonRings<'_>> {
+        fn to_rings(rings: &[LinearRing]) -> Option<PolygonRings<'_>> {
+            rings
+                .split_first()
+                .map(|(exterior, interiors)| PolygonRings {
+                    exterior,
+                    interiors,
+                })
+        }
+        match self {
+            Self::Polygon { coordinates } => to_rings(coordinates).into_iter().collect(),
+            Self::MultiPolygon { coordinates } => {
+                coordinates.iter().filter_map(|p| to_rings(p)).collect()
+            }
+            Self::GeometryCollection { geometries } => {
+                geometries.iter().flat_map(|g| g.polygons()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// 含まれるポリゴンの環の列（最初が外周、残りが穴）を全て変更可能な形で取り出す。
+    pub fn polygons_mut(&mut self) -> Vec<&mut Vec<LinearRing>> {
+        match self {
+            Self::Polygon { coordinates } => vec![coordinates],
+            Self::MultiPolygon { coordinates } => coordinates.iter_mut().collect(),
+            Self::GeometryCollection { geometries } => geometries
+                .iter_mut()
+                .flat_map(|g| g.polygons_mut())
+                .collect(),
+            _ => vec![],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct GeoFeature {
     #[serde(rename = "type")]
     pub _type: String,
@@ -37,3 +107,51 @@ pub struct GeoJson {
     pub crs: Crs,
     pub features: Vec<GeoFeature>,
 }
+
+/// 任意のGeoJSONのFeatureCollection。`name`や`crs`が無くてもよく、プロパティの値はどの型でもよい。
+#[derive(Deserialize, Debug)]
+pub struct FeatureCollection {
+    pub features: Vec<AnyFeature>,
+}
+
+/// 任意のGeoJSONのFeature。
+#[derive(Deserialize, Debug)]
+pub struct AnyFeature {
+    #[serde(default)]
+    pub properties: Option<serde_json::Map<String, serde_json::Value>>,
+    pub geometry: Option<FeatureGeometry>,
+}
+
+impl FeatureCollection {
+    /// ジオメトリを持つFeatureを`GeoFeature`にする。
+    pub fn into_geo_features(self) -> Vec<GeoFeature> {
+        self.features
+            .into_iter()
+            .filter_map(|feat| {
+                Some(GeoFeature {
+                    _type: "Feature".to_string(),
+                    properties: string_properties(feat.properties.unwrap_or_default()),
+                    geometry: feat.geometry?,
+                })
+            })
+            .collect()
+    }
+}
+
+/// 任意の型のプロパティの値を文字列にする。
+/// 文字列ならそのまま、`null`なら`None`、それ以外はJSONの表記の文字列とする。
+pub fn string_properties(
+    properties: serde_json::Map<String, serde_json::Value>,
+) -> HashMap<String, Option<String>> {
+    properties
+        .into_iter()
+        .map(|(k, v)| {
+            let v = match v {
+                serde_json::Value::Null => None,
+                serde_json::Value::String(s) => Some(s),
+                other => Some(other.to_string()),
+            };
+            (k, v)
+        })
+        .collect()
+}
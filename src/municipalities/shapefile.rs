@@ -0,0 +1,487 @@
+//! ESRI Shapefile（.shp/.dbf）のポリゴンと属性を読み込み、GeoJSONと同じ`GeoFeature`にする
+
+use std::{collections::HashMap, fs, path::Path};
+
+use encoding_rs::SHIFT_JIS;
+
+use super::serde_models::{FeatureGeometry, GeoFeature, LinearRing};
+use crate::error::{Error, Result};
+
+/// .shpのファイルコード
+const SHP_FILE_CODE: i32 = 9994;
+/// .shpのヘッダの長さ
+const SHP_HEADER_LEN: usize = 100;
+/// DBFのフィールド記述子の終端
+const DBF_HEADER_TERMINATOR: u8 = 0x0D;
+/// DBFの削除済みレコードの印
+const DBF_DELETED: u8 = b'*';
+
+/// DBFの一つのレコードの属性。`GeoFeature`のプロパティと同じ形
+type Attributes = HashMap<String, Option<String>>;
+
+/// DBFの文字列の符号化方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DbfEncoding {
+    Utf8,
+    /// Shift_JIS（Windows-31J）。国土数値情報のShapefileの多くはこれ
+    ShiftJis,
+}
+
+impl DbfEncoding {
+    /// .cpgファイルの内容から符号化方式を決める。知らない名前なら`None`を返す。
+    pub fn from_cpg(cpg: &str) -> Option<Self> {
+        match cpg.trim().to_ascii_uppercase().as_str() {
+            "UTF-8" | "UTF8" | "65001" => Some(Self::Utf8),
+            "SHIFT_JIS" | "SHIFT-JIS" | "SJIS" | "CP932" | "932" | "MS932" | "WINDOWS-31J" => {
+                Some(Self::ShiftJis)
+            }
+            _ => None,
+        }
+    }
+
+    /// DBFのヘッダの言語ドライバIDから符号化方式を決める。日本語のもの以外は`None`を返す。
+    fn from_language_driver(id: u8) -> Option<Self> {
+        match id {
+            0x13 | 0x7B => Some(Self::ShiftJis),
+            _ => None,
+        }
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Self::ShiftJis => SHIFT_JIS.decode_without_bom_handling(bytes).0.into_owned(),
+        }
+    }
+}
+
+/// バイト列を読み進める。範囲外を読もうとした場合はエラーとする。
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    path: &'a str,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let slice = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid(self.path, "unexpected end of file"))?;
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn i32_be(&mut self) -> Result<i32> {
+        Ok(i32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn i32_le(&mut self) -> Result<i32> {
+        Ok(i32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64_le(&mut self) -> Result<f64> {
+        Ok(f64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    /// 負の値を許さない長さや個数を読む。
+    fn count_le(&mut self) -> Result<usize> {
+        usize::try_from(self.i32_le()?).map_err(|_| invalid(self.path, "negative count"))
+    }
+}
+
+fn invalid(path: &str, reason: impl Into<String>) -> Error {
+    Error::InvalidShapefile {
+        path: path.to_string(),
+        reason: reason.into(),
+    }
+}
+
+/// .shpとそれと同じ名前の.dbfを読み込み、ジオメトリを持つレコードを`GeoFeature`にする。
+/// 属性の符号化方式は.cpgがあればそれに従い、無ければDBFの言語ドライバIDと内容から推定する。
+/// 削除済みのレコードとNull Shapeのレコードは除く。
+pub fn read_shapefile(path: &Path) -> Result<Vec<GeoFeature>> {
+    let dbf_path = path.with_extension("dbf");
+    let (shp_name, dbf_name) = (path.display().to_string(), dbf_path.display().to_string());
+    let shp = fs::read(path).map_err(Error::io(&shp_name))?;
+    let dbf = fs::read(&dbf_path).map_err(Error::io(&dbf_name))?;
+    let encoding = fs::read_to_string(path.with_extension("cpg"))
+        .ok()
+        .and_then(|cpg| DbfEncoding::from_cpg(&cpg));
+
+    let geometries = parse_shp(&shp, &shp_name)?;
+    let records = parse_dbf(&dbf, encoding, &dbf_name)?;
+    if geometries.len() != records.len() {
+        return Err(invalid(
+            &shp_name,
+            format!(
+                "{} shapes but {} attribute records",
+                geometries.len(),
+                records.len()
+            ),
+        ));
+    }
+    Ok(geometries
+        .into_iter()
+        .zip(records)
+        .filter_map(|(geometry, properties)| {
+            Some(GeoFeature {
+                _type: "Feature".to_string(),
+                properties: properties?,
+                geometry: geometry?,
+            })
+        })
+        .collect())
+}
+
+/// .shpの内容からレコードごとのジオメトリを返す。Null Shapeは`None`とする。
+/// ポリゴン（PolygonZ、PolygonMを含む）以外の形状はエラーとする。
+pub fn parse_shp(bytes: &[u8], path: &str) -> Result<Vec<Option<FeatureGeometry>>> {
+    let mut header = Reader {
+        bytes,
+        pos: 0,
+        path,
+    };
+    if header.i32_be()? != SHP_FILE_CODE {
+        return Err(invalid(path, "not a shapefile"));
+    }
+    let mut geometries = vec![];
+    let mut pos = SHP_HEADER_LEN;
+    while pos < bytes.len() {
+        let mut record = Reader { bytes, pos, path };
+        let _record_number = record.i32_be()?;
+        // 長さは16bit語の数
+        let content_len = usize::try_from(record.i32_be()?)
+            .map_err(|_| invalid(path, "negative record length"))?
+            * 2;
+        let content = record.take(content_len)?;
+        pos = record.pos;
+        geometries.push(parse_shp_record(content, path)?);
+    }
+    Ok(geometries)
+}
+
+/// .shpの一つのレコードの内容を読む。
+fn parse_shp_record(content: &[u8], path: &str) -> Result<Option<FeatureGeometry>> {
+    let mut reader = Reader {
+        bytes: content,
+        pos: 0,
+        path,
+    };
+    match reader.i32_le()? {
+        0 => return Ok(None),
+        // Polygon、PolygonZ、PolygonM。Z・Mの値はx, yの後ろにあるので読まずに捨てる
+        5 | 15 | 25 => {}
+        other => return Err(invalid(path, format!("unsupported shape type {}", other))),
+    }
+    reader.take(32)?; // 外接矩形
+    let num_parts = reader.count_le()?;
+    let num_points = reader.count_le()?;
+    let mut parts = (0..num_parts)
+        .map(|_| reader.count_le())
+        .collect::<Result<Vec<_>>>()?;
+    parts.push(num_points);
+    let points = (0..num_points)
+        .map(|_| Ok(vec![reader.f64_le()?, reader.f64_le()?]))
+        .collect::<Result<Vec<_>>>()?;
+    let rings = parts
+        .windows(2)
+        .map(|w| {
+            points
+                .get(w[0]..w[1])
+                .map(|ring| ring.to_vec())
+                .ok_or_else(|| invalid(path, "invalid part index"))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Some(rings_to_geometry(rings)))
+}
+
+/// 環の符号付き面積（反時計回りが正）
+fn ring_area(ring: &LinearRing) -> f64 {
+    ring.windows(2)
+        .map(|w| w[0][0] * w[1][1] - w[1][0] * w[0][1])
+        .sum::<f64>()
+        / 2.0
+}
+
+/// 点が環の内側にあるかどうかを返す。
+fn ring_contains(ring: &LinearRing, p: &[f64]) -> bool {
+    let mut inside = false;
+    for w in ring.windows(2) {
+        let (a, b) = (&w[0], &w[1]);
+        if (a[1] > p[1]) != (b[1] > p[1])
+            && p[0] < a[0] + (p[1] - a[1]) * (b[0] - a[0]) / (b[1] - a[1])
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// Shapefileの環の列をポリゴンにまとめる。時計回りの環を外周、反時計回りの環を穴とし、
+/// 穴はその最初の点を含む外周のうち面積最小のもの（湖の中の島の穴なら島）に、
+/// 含む外周が無ければ直前の外周に属させる。環の向きはそのまま保つ。
+fn rings_to_geometry(rings: Vec<LinearRing>) -> FeatureGeometry {
+    let (exteriors, holes): (Vec<_>, Vec<_>) = rings
+        .into_iter()
+        .enumerate()
+        .partition(|(_, ring)| ring_area(ring) <= 0.0);
+    if exteriors.is_empty() {
+        // 向きが逆の不正なデータは、全ての環を外周とみなす
+        let coordinates = holes.into_iter().map(|(_, ring)| vec![ring]).collect();
+        return FeatureGeometry::MultiPolygon { coordinates };
+    }
+    let mut polygons = exteriors
+        .into_iter()
+        .map(|(idx, ring)| (idx, vec![ring]))
+        .collect::<Vec<_>>();
+    for (hole_idx, hole) in holes {
+        let owner = polygons
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, rings))| hole.first().is_some_and(|p| ring_contains(&rings[0], p)))
+            .min_by(|(_, (_, a)), (_, (_, b))| {
+                ring_area(&a[0]).abs().total_cmp(&ring_area(&b[0]).abs())
+            })
+            .map(|(owner, _)| owner)
+            .or_else(|| polygons.iter().rposition(|(idx, _)| *idx < hole_idx))
+            .unwrap_or(0);
+        polygons[owner].1.push(hole);
+    }
+    let mut coordinates = polygons
+        .into_iter()
+        .map(|(_, rings)| rings)
+        .collect::<Vec<_>>();
+    if coordinates.len() == 1 {
+        FeatureGeometry::Polygon {
+            coordinates: coordinates.pop().unwrap(),
+        }
+    } else {
+        FeatureGeometry::MultiPolygon { coordinates }
+    }
+}
+
+/// DBFの内容からレコードごとの属性を返す。削除済みのレコードは`None`とする。
+/// 値は前後の空白を除いた文字列とし、空なら`None`とする。
+/// `encoding`が`None`の場合は言語ドライバIDから、それも無ければUTF-8として正しいかどうかで決める。
+pub fn parse_dbf(
+    bytes: &[u8],
+    encoding: Option<DbfEncoding>,
+    path: &str,
+) -> Result<Vec<Option<Attributes>>> {
+    let mut header = Reader {
+        bytes,
+        pos: 4,
+        path,
+    };
+    let num_records = u32::from_le_bytes(header.take(4)?.try_into().unwrap()) as usize;
+    let header_len = u16::from_le_bytes(header.take(2)?.try_into().unwrap()) as usize;
+    let record_len = u16::from_le_bytes(header.take(2)?.try_into().unwrap()) as usize;
+    let language_driver = *bytes
+        .get(29)
+        .ok_or_else(|| invalid(path, "header too short"))?;
+
+    // フィールド記述子（名前、長さ）
+    let mut fields = vec![];
+    let mut pos = 32;
+    while bytes.get(pos).is_some_and(|&b| b != DBF_HEADER_TERMINATOR) && pos + 32 <= header_len {
+        let descriptor = bytes
+            .get(pos..pos + 32)
+            .ok_or_else(|| invalid(path, "field descriptor truncated"))?;
+        let name_len = descriptor[..11].iter().position(|&b| b == 0).unwrap_or(11);
+        let name = String::from_utf8_lossy(&descriptor[..name_len]).into_owned();
+        fields.push((name, descriptor[16] as usize));
+        pos += 32;
+    }
+
+    let records_area = bytes
+        .get(header_len..header_len + num_records * record_len)
+        .ok_or_else(|| invalid(path, "unexpected end of file"))?;
+    let encoding = encoding
+        .or_else(|| DbfEncoding::from_language_driver(language_driver))
+        .unwrap_or_else(|| match std::str::from_utf8(records_area) {
+            Ok(_) => DbfEncoding::Utf8,
+            Err(_) => DbfEncoding::ShiftJis,
+        });
+
+    let mut records = vec![];
+    for record in records_area.chunks_exact(record_len.max(1)) {
+        if record[0] == DBF_DELETED {
+            records.push(None);
+            continue;
+        }
+        let mut props = HashMap::new();
+        let mut offset = 1;
+        for (name, len) in fields.iter() {
+            let raw = record
+                .get(offset..offset + len)
+                .ok_or_else(|| invalid(path, "field exceeds record length"))?;
+            offset += len;
+            let value = encoding.decode(raw);
+            let value = value.trim_matches(|c: char| c == ' ' || c == '\0');
+            props.insert(name.clone(), (!value.is_empty()).then(|| value.to_string()));
+        }
+        records.push(Some(props));
+    }
+    Ok(records)
+}
+
+/// 同じ内容のGeoJSONとShapefile（Shift_JISとUTF-8）から同じ`GeoFeature`が得られることを確かめる。
+#[test]
+fn test_read_shapefile() {
+    use std::fs::write;
+
+    use encoding_rs::UTF_8;
+
+    use super::serde_models::GeoJson;
+
+    // 時計回りの外周に反時計回りの穴を持つ自治体と、二つの島からなる自治体
+    let exterior = vec![[0.0, 0.0], [0.0, 4.0], [4.0, 4.0], [4.0, 0.0], [0.0, 0.0]];
+    let hole = vec![[1.0, 1.0], [2.0, 1.0], [2.0, 2.0], [1.0, 1.0]];
+    let island = |x: f64| vec![[x, 0.0], [x, 1.0], [x + 1.0, 1.0], [x, 0.0]];
+    let shapes: Vec<Vec<Vec<[f64; 2]>>> = vec![
+        vec![exterior.clone(), hole.clone()],
+        vec![],
+        vec![island(10.0), island(20.0)],
+    ];
+    let attributes = [
+        ("兵庫県", "丹波篠山市", "28221"),
+        ("削除", "削除", "00000"),
+        ("広島県", "府中町", ""),
+    ];
+
+    // .shpを組み立てる。2番目のレコードはNull Shapeとし、DBFでは削除済みとする
+    let mut shp = vec![0u8; SHP_HEADER_LEN];
+    shp[..4].copy_from_slice(&SHP_FILE_CODE.to_be_bytes());
+    shp[28..32].copy_from_slice(&1000i32.to_le_bytes());
+    shp[32..36].copy_from_slice(&5i32.to_le_bytes());
+    for (idx, rings) in shapes.iter().enumerate() {
+        let mut content = vec![];
+        if rings.is_empty() {
+            content.extend(0i32.to_le_bytes());
+        } else {
+            let points = rings.concat();
+            content.extend(5i32.to_le_bytes());
+            content.extend([0u8; 32]);
+            content.extend((rings.len() as i32).to_le_bytes());
+            content.extend((points.len() as i32).to_le_bytes());
+            let mut start = 0;
+            for ring in rings.iter() {
+                content.extend((start as i32).to_le_bytes());
+                start += ring.len();
+            }
+            for [x, y] in points {
+                content.extend(x.to_le_bytes());
+                content.extend(y.to_le_bytes());
+            }
+        }
+        shp.extend((idx as i32 + 1).to_be_bytes());
+        shp.extend((content.len() as i32 / 2).to_be_bytes());
+        shp.extend(content);
+    }
+    let shp_len = (shp.len() as i32 / 2).to_be_bytes();
+    shp[24..28].copy_from_slice(&shp_len);
+
+    // .dbfを組み立てる
+    let dbf = |encoding: &'static encoding_rs::Encoding, language_driver: u8| {
+        let fields = [("N03_001", 20), ("N03_004", 30), ("N03_007", 5)];
+        let record_len = 1 + fields.iter().map(|(_, len)| len).sum::<usize>();
+        let header_len = 32 + 32 * fields.len() + 1;
+        let mut dbf = vec![0u8; 32];
+        dbf[0] = 0x03;
+        dbf[4..8].copy_from_slice(&(attributes.len() as u32).to_le_bytes());
+        dbf[8..10].copy_from_slice(&(header_len as u16).to_le_bytes());
+        dbf[10..12].copy_from_slice(&(record_len as u16).to_le_bytes());
+        dbf[29] = language_driver;
+        for (name, len) in fields {
+            let mut descriptor = [0u8; 32];
+            descriptor[..name.len()].copy_from_slice(name.as_bytes());
+            descriptor[11] = b'C';
+            descriptor[16] = len as u8;
+            dbf.extend(descriptor);
+        }
+        dbf.push(DBF_HEADER_TERMINATOR);
+        for (idx, (pref, muni, code)) in attributes.iter().enumerate() {
+            dbf.push(if idx == 1 { DBF_DELETED } else { b' ' });
+            for (value, (_, len)) in [pref, muni, code].iter().zip(fields) {
+                let mut field = encoding.encode(value).0.into_owned();
+                field.resize(len, b' ');
+                dbf.extend(field);
+            }
+        }
+        dbf.push(0x1A);
+        dbf
+    };
+
+    let geojson = r#"{ "type": "FeatureCollection", "name": "test", "crs": { "type": "name", "properties": { "name": "urn:ogc:def:crs:EPSG::6668" } }, "features": [
+        { "type": "Feature", "properties": { "N03_001": "兵庫県", "N03_004": "丹波篠山市", "N03_007": "28221" }, "geometry": { "type": "Polygon", "coordinates": [ [ [0.0, 0.0], [0.0, 4.0], [4.0, 4.0], [4.0, 0.0], [0.0, 0.0] ], [ [1.0, 1.0], [2.0, 1.0], [2.0, 2.0], [1.0, 1.0] ] ] } },
+        { "type": "Feature", "properties": { "N03_001": "広島県", "N03_004": "府中町", "N03_007": null }, "geometry": { "type": "MultiPolygon", "coordinates": [ [ [ [10.0, 0.0], [10.0, 1.0], [11.0, 1.0], [10.0, 0.0] ] ], [ [ [20.0, 0.0], [20.0, 1.0], [21.0, 1.0], [20.0, 0.0] ] ] ] } }
+    ] }"#;
+    let expected =
+        serde_json::to_value(serde_json::from_str::<GeoJson>(geojson).unwrap().features).unwrap();
+
+    let dir = crate::test::TempDir::new("read_shapefile");
+    let cases = [
+        ("sjis_ldid", SHIFT_JIS, 0x13, None),
+        ("sjis_guess", SHIFT_JIS, 0x00, None),
+        ("sjis_cpg", SHIFT_JIS, 0x00, Some("CP932")),
+        ("utf8", UTF_8, 0x00, None),
+        ("utf8_cpg", UTF_8, 0x13, Some("UTF-8")),
+    ];
+    for (name, encoding, language_driver, cpg) in cases {
+        let path = dir.path().join(format!("{}.shp", name));
+        write(&path, &shp).unwrap();
+        write(path.with_extension("dbf"), dbf(encoding, language_driver)).unwrap();
+        if let Some(cpg) = cpg {
+            write(path.with_extension("cpg"), cpg).unwrap();
+        }
+        let features = read_shapefile(&path).unwrap();
+        assert_eq!(
+            serde_json::to_value(features).unwrap(),
+            expected,
+            "{}",
+            name
+        );
+    }
+
+    assert!(parse_shp(&[0u8; SHP_HEADER_LEN], "zero.shp").is_err());
+    assert!(parse_shp(&shp[..shp.len() - 8], "truncated.shp").is_err());
+    // ヘッダの長さより前でフィールド記述子が途切れている
+    let dbf = dbf(UTF_8, 0x13);
+    assert!(matches!(
+        parse_dbf(&dbf[..48], None, "truncated.dbf"),
+        Err(Error::InvalidShapefile { .. })
+    ));
+}
+
+/// 湖の中の島に穴がある場合、島の穴は島に、湖は外側の外周に属することを確かめる。
+#[test]
+fn test_hole_assignment() {
+    // 時計回りの正方形と反時計回りの正方形
+    let clockwise = |min: f64, max: f64| {
+        vec![
+            vec![min, min],
+            vec![min, max],
+            vec![max, max],
+            vec![max, min],
+            vec![min, min],
+        ]
+    };
+    let counterclockwise = |min: f64, max: f64| {
+        let mut ring = clockwise(min, max);
+        ring.reverse();
+        ring
+    };
+    let (outer, lake) = (clockwise(0.0, 10.0), counterclockwise(1.0, 9.0));
+    let (island, pond) = (clockwise(3.0, 7.0), counterclockwise(4.0, 5.0));
+    let geometry = rings_to_geometry(vec![
+        outer.clone(),
+        lake.clone(),
+        island.clone(),
+        pond.clone(),
+    ]);
+    assert!(matches!(
+        geometry,
+        FeatureGeometry::MultiPolygon { coordinates } if coordinates == vec![vec![outer, lake], vec![island, pond]]
+    ));
+}
@@ -0,0 +1,293 @@
+//! TopoJSONの共有された弧と量子化された座標を展開し、`GeoFeature`にする
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use super::serde_models::{string_properties, FeatureGeometry, GeoFeature, LinearRing, Position};
+use crate::error::{Error, Result};
+
+/// 量子化された座標を元に戻す変換。`x * scale + translate`とする
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub scale: [f64; 2],
+    pub translate: [f64; 2],
+}
+
+impl Transform {
+    fn apply(&self, x: f64, y: f64) -> Position {
+        vec![
+            x * self.scale[0] + self.translate[0],
+            y * self.scale[1] + self.translate[1],
+        ]
+    }
+}
+
+/// TopoJSONのトポロジ
+#[derive(Deserialize, Debug)]
+pub struct Topology {
+    pub transform: Option<Transform>,
+    /// 弧の列。`transform`がある場合、各弧の2点目以降は直前の点からの差分で表される
+    pub arcs: Vec<Vec<Vec<f64>>>,
+    /// 名前ごとのオブジェクト。名前の順に展開する
+    pub objects: BTreeMap<String, TopoGeometry>,
+}
+
+/// 弧の添字で表されたジオメトリと、そのプロパティ
+#[derive(Deserialize, Debug)]
+pub struct TopoGeometry {
+    #[serde(flatten)]
+    pub shape: TopoShape,
+    #[serde(default)]
+    pub properties: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+/// `type`フィールドで種類が区別されるジオメトリ。
+/// 弧の添字が負の場合は、`!i`番目の弧を逆向きにたどる
+#[derive(Deserialize, Debug)]
+#[serde(tag = "type")]
+pub enum TopoShape {
+    Point { coordinates: Position },
+    MultiPoint { coordinates: Vec<Position> },
+    LineString { arcs: Vec<i64> },
+    MultiLineString { arcs: Vec<Vec<i64>> },
+    Polygon { arcs: Vec<Vec<i64>> },
+    MultiPolygon { arcs: Vec<Vec<Vec<i64>>> },
+    GeometryCollection { geometries: Vec<TopoGeometry> },
+}
+
+impl Topology {
+    /// `object`の名前のオブジェクト（`None`なら全てのオブジェクト）をFeatureの列にする。
+    /// GeometryCollectionのオブジェクトはその要素ごとにFeatureとする。
+    pub fn features(&self, object: Option<&str>) -> Result<Vec<GeoFeature>> {
+        let arcs = self.decode_arcs()?;
+        let mut features = vec![];
+        for (name, geometry) in self.objects.iter() {
+            if object.is_some_and(|object| object != name) {
+                continue;
+            }
+            let members = match &geometry.shape {
+                TopoShape::GeometryCollection { geometries } => geometries.iter().collect(),
+                _ => vec![geometry],
+            };
+            for member in members {
+                features.push(GeoFeature {
+                    _type: "Feature".to_string(),
+                    properties: string_properties(member.properties.clone().unwrap_or_default()),
+                    geometry: self.geometry(&member.shape, &arcs, name)?,
+                });
+            }
+        }
+        if let Some(object) = object.filter(|object| !self.objects.contains_key(*object)) {
+            return Err(Error::InvalidTopology {
+                object: object.to_string(),
+                reason: "no such object".to_string(),
+            });
+        }
+        Ok(features)
+    }
+
+    /// 全ての弧の差分符号化と量子化を元に戻す。座標が2次元に満たない点があればエラーとする。
+    fn decode_arcs(&self) -> Result<Vec<Vec<Position>>> {
+        self.arcs
+            .iter()
+            .enumerate()
+            .map(|(arc_idx, arc)| {
+                let (mut x, mut y) = (0.0, 0.0);
+                arc.iter()
+                    .enumerate()
+                    .map(|(position_idx, delta)| {
+                        let [dx, dy, ..] = delta[..] else {
+                            return Err(Error::InvalidTopology {
+                                object: "arcs".to_string(),
+                                reason: format!(
+                                    "position {} of arc {} has fewer than 2 coordinates",
+                                    position_idx, arc_idx
+                                ),
+                            });
+                        };
+                        let Some(transform) = self.transform else {
+                            return Ok(delta.clone());
+                        };
+                        x += dx;
+                        y += dy;
+                        Ok(transform.apply(x, y))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// 弧の添字の列をつなげて一つの点列にする。つなぎ目で重なる点は一つにする。
+    fn stitch(&self, indices: &[i64], arcs: &[Vec<Position>], object: &str) -> Result<LinearRing> {
+        let mut points: Vec<Position> = vec![];
+        for &idx in indices {
+            let (arc_idx, reversed) = if idx < 0 { (!idx, true) } else { (idx, false) };
+            let arc = usize::try_from(arc_idx)
+                .ok()
+                .and_then(|i| arcs.get(i))
+                .ok_or_else(|| Error::InvalidTopology {
+                    object: object.to_string(),
+                    reason: format!("arc index {} out of range", idx),
+                })?;
+            let mut arc = arc.clone();
+            if reversed {
+                arc.reverse();
+            }
+            let skip = usize::from(!points.is_empty());
+            points.extend(arc.into_iter().skip(skip));
+        }
+        Ok(points)
+    }
+
+    /// 弧の添字で表されたジオメトリを座標で表されたジオメトリにする。
+    fn geometry(
+        &self,
+        shape: &TopoShape,
+        arcs: &[Vec<Position>],
+        object: &str,
+    ) -> Result<FeatureGeometry> {
+        let stitch_all = |lines: &[Vec<i64>]| {
+            lines
+                .iter()
+                .map(|line| self.stitch(line, arcs, object))
+                .collect::<Result<Vec<_>>>()
+        };
+        // 点は差分符号化されていないので量子化のみを戻す
+        let point = |p: &Position| {
+            let [x, y, ..] = p[..] else {
+                return Err(Error::InvalidTopology {
+                    object: object.to_string(),
+                    reason: "position has fewer than 2 coordinates".to_string(),
+                });
+            };
+            Ok(match self.transform {
+                Some(transform) => transform.apply(x, y),
+                None => p.clone(),
+            })
+        };
+        Ok(match shape {
+            TopoShape::Point { coordinates } => FeatureGeometry::Point {
+                coordinates: point(coordinates)?,
+            },
+            TopoShape::MultiPoint { coordinates } => FeatureGeometry::MultiPoint {
+                coordinates: coordinates.iter().map(point).collect::<Result<_>>()?,
+            },
+            TopoShape::LineString { arcs: line } => FeatureGeometry::LineString {
+                coordinates: self.stitch(line, arcs, object)?,
+            },
+            TopoShape::MultiLineString { arcs: lines } => FeatureGeometry::MultiLineString {
+                coordinates: stitch_all(lines)?,
+            },
+            TopoShape::Polygon { arcs: rings } => FeatureGeometry::Polygon {
+                coordinates: stitch_all(rings)?,
+            },
+            TopoShape::MultiPolygon { arcs: polygons } => FeatureGeometry::MultiPolygon {
+                coordinates: polygons
+                    .iter()
+                    .map(|rings| stitch_all(rings))
+                    .collect::<Result<_>>()?,
+            },
+            TopoShape::GeometryCollection { geometries } => FeatureGeometry::GeometryCollection {
+                geometries: geometries
+                    .iter()
+                    .map(|g| self.geometry(&g.shape, arcs, object))
+                    .collect::<Result<_>>()?,
+            },
+        })
+    }
+}
+
+/// 境界を共有する二つの正方形の自治体を、差分符号化と変換を含むTopoJSONから展開できることを確かめる。
+#[test]
+fn test_topology_features() {
+    use crate::shapes::{merge_features, ShapeOptions};
+
+    // 弧0: (1,0)->(1,1) 共有する境界、弧1: (1,1)->(0,1)->(0,0)->(1,0)、弧2: (1,0)->(2,0)->(2,1)->(1,1)
+    // （量子化された座標）。量子化の単位は0.5、原点は(135, 35)
+    let json = r#"{
+        "type": "Topology",
+        "transform": { "scale": [0.5, 0.5], "translate": [135.0, 35.0] },
+        "arcs": [
+            [[1, 0], [0, 1]],
+            [[1, 1], [-1, 0], [0, -1], [1, 0]],
+            [[1, 0], [1, 0], [0, 1], [-1, 0]]
+        ],
+        "objects": {
+            "towns": {
+                "type": "GeometryCollection",
+                "geometries": [
+                    { "type": "Polygon", "arcs": [[0, 1]], "properties": { "name": "西町", "code": 1 } },
+                    { "type": "MultiPolygon", "arcs": [[[2, -1]]], "properties": { "name": "東町", "code": 2 } },
+                    { "type": "LineString", "arcs": [-1] }
+                ]
+            },
+            "office": { "type": "Point", "coordinates": [1, 1], "properties": { "name": "役場" } }
+        }
+    }"#;
+    let topology = serde_json::from_str::<Topology>(json).unwrap();
+    let features = topology.features(Some("towns")).unwrap();
+    assert_eq!(features.len(), 3);
+    assert_eq!(features[0].properties["name"].as_deref(), Some("西町"));
+    assert_eq!(features[1].properties["code"].as_deref(), Some("2"));
+
+    let west = features[0].geometry.polygons();
+    assert_eq!(
+        *west[0].exterior,
+        vec![
+            vec![135.5, 35.0],
+            vec![135.5, 35.5],
+            vec![135.0, 35.5],
+            vec![135.0, 35.0],
+            vec![135.5, 35.0]
+        ]
+    );
+    let east = features[1].geometry.polygons();
+    assert_eq!(
+        *east[0].exterior,
+        vec![
+            vec![135.5, 35.0],
+            vec![136.0, 35.0],
+            vec![136.0, 35.5],
+            vec![135.5, 35.5],
+            vec![135.5, 35.0]
+        ]
+    );
+    assert!(matches!(
+        &features[2].geometry,
+        FeatureGeometry::LineString { coordinates } if *coordinates == vec![vec![135.5, 35.5], vec![135.5, 35.0]]
+    ));
+
+    // 全てのオブジェクトを名前の順に展開する。点は差分符号化されていない
+    let all = topology.features(None).unwrap();
+    assert_eq!(all.len(), 4);
+    assert!(matches!(
+        &all[0].geometry,
+        FeatureGeometry::Point { coordinates } if *coordinates == vec![135.5, 35.5]
+    ));
+    let shape = merge_features("西町", &[&all[1]], &ShapeOptions::new(16)).unwrap();
+    assert_eq!(shape.outline().len(), 16);
+
+    assert!(topology.features(Some("missing")).is_err());
+    let broken = r#"{ "type": "Topology", "arcs": [], "objects": { "a": { "type": "Polygon", "arcs": [[3]] } } }"#;
+    let broken = serde_json::from_str::<Topology>(broken).unwrap();
+    assert!(matches!(
+        broken.features(None),
+        Err(Error::InvalidTopology { object, .. }) if object == "a"
+    ));
+    // 座標が2次元に満たない点は弧でも点でもエラーになる
+    let short_arc = r#"{ "type": "Topology", "transform": { "scale": [1, 1], "translate": [0, 0] },
+        "arcs": [[[0, 0], [1]]], "objects": { "a": { "type": "LineString", "arcs": [0] } } }"#;
+    let short_arc = serde_json::from_str::<Topology>(short_arc).unwrap();
+    assert!(matches!(
+        short_arc.features(None),
+        Err(Error::InvalidTopology { object, .. }) if object == "arcs"
+    ));
+    let short_point = r#"{ "type": "Topology", "transform": { "scale": [1, 1], "translate": [0, 0] },
+        "arcs": [], "objects": { "p": { "type": "MultiPoint", "coordinates": [[0, 0], [1]] } } }"#;
+    let short_point = serde_json::from_str::<Topology>(short_point).unwrap();
+    assert!(matches!(
+        short_point.features(None),
+        Err(Error::InvalidTopology { object, .. }) if object == "p"
+    ));
+}
@@ -1,91 +1,403 @@
 //! GeoJsonの座標情報を取り出しFFT可能な状態に置き換える
 
-use std::collections::HashMap;
+use std::{collections::HashMap, str::FromStr};
 
-use anyhow::Result;
 use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
 
-use super::data::ORDINANCE_DISIGNATED_CITIES;
-use super::serde_models::GeoFeature;
-use crate::shapes::ShapePoints;
+use super::data::{ORDINANCE_DISIGNATED_CITIES, PREFECTURES};
+use super::dataset::Dataset;
+use super::mapping::PropertyMapping;
+use super::serde_models::{GeoFeature, LinearRing};
+use crate::{
+    error::{Error, Result},
+    fft::FftNormalization,
+    geometry::perimeter,
+    io::read_municipalities_boundary_data,
+    shapes::ShapePoints,
+    spectrum::Spectrum,
+};
 
-pub fn geo_feature_props_to_array(
+/// 複数の環を持つ形状で、一つの環に割り当てる最小の点の数
+const MIN_CONTOUR_POINTS: usize = 4;
+
+/// GISデータのプロパティを`N03_001`〜`N03_007`の順の配列にする。それ以外のキーは無視する。
+pub fn geo_feature_props_to_array(props: &HashMap<String, Option<String>>) -> [Option<String>; 7] {
+    std::array::from_fn(|idx| props.get(&format!("N03_00{}", idx + 1)).cloned().flatten())
+}
+
+/// GISデータのプロパティから、N03の対応付けに従って自治体名を返す。
+pub fn geo_feature_props_to_name(props: &HashMap<String, Option<String>>) -> String {
+    PropertyMapping::n03().name(props)
+}
+
+/// 複数の自治体をまとめた区域の単位
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaLevel {
+    /// 政令指定都市（N03_003）。区をまとめる
+    DesignatedCity,
+    /// 郡（N03_003）
+    County,
+    /// 北海道の振興局（N03_002）
+    Subprefecture,
+}
+
+impl FromStr for AreaLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "city" => Ok(Self::DesignatedCity),
+            "county" => Ok(Self::County),
+            "subpref" => Ok(Self::Subprefecture),
+            other => Err(Error::InvalidOption {
+                kind: "area level",
+                value: other.to_string(),
+            }),
+        }
+    }
+}
+
+impl AreaLevel {
+    pub const ALL: [Self; 3] = [Self::DesignatedCity, Self::County, Self::Subprefecture];
+
+    /// プロパティの配列から、この単位で見た区域名（例: "兵庫県神戸市"、"京都府与謝郡"、"北海道石狩振興局"）を返す。
+    /// Featureがこの単位の区域に属さない場合は`None`を返す。
+    pub fn area_name(&self, array: &[Option<String>; 7]) -> Option<String> {
+        let prefecture = array[0].as_deref().unwrap_or_default();
+        let area = match self {
+            Self::DesignatedCity => array[2]
+                .as_deref()
+                .filter(|v| ORDINANCE_DISIGNATED_CITIES.contains(v))?,
+            Self::County => array[2].as_deref().filter(|v| v.ends_with('郡'))?,
+            Self::Subprefecture => array[1].as_deref()?,
+        };
+        Some(format!("{}{}", prefecture, area))
+    }
+}
+
+/// GISデータのプロパティから、`level`の単位で見た区域名を返す。区域に属さない場合は`None`を返す。
+pub fn geo_feature_props_to_area_name(
     props: &HashMap<String, Option<String>>,
-) -> Result<[Option<String>; 7]> {
-    let mut result: [Option<String>; 7] = [(); 7].map(|_| None);
-    for (k, v) in props.iter() {
-        let replaced_k = k.replace("N03_00", "");
-        let num_k = replaced_k.parse::<usize>()?;
-        result[num_k - 1] = v.clone();
+    level: AreaLevel,
+) -> Option<String> {
+    level.area_name(&geo_feature_props_to_array(props))
+}
+
+/// GISデータのプロパティから行政区域コード（N03_007）を返す。
+pub fn geo_feature_props_to_code(props: &HashMap<String, Option<String>>) -> Option<String> {
+    PropertyMapping::n03().id(props)
+}
+
+/// 文字列が5桁の行政区域コードの形をしているかどうかを返す。
+pub fn is_muni_code(s: &str) -> bool {
+    s.len() == 5 && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// 行政区域コードの上2桁（都道府県コード）から都道府県名を返す。
+pub fn prefecture_name_from_code(muni_code: &str) -> Result<&'static str> {
+    muni_code
+        .get(..2)
+        .and_then(|id| id.parse::<usize>().ok())
+        .filter(|&id| id > 0)
+        .and_then(|id| PREFECTURES.get(id))
+        .copied()
+        .ok_or_else(|| Error::InvalidMunicipalityCode(muni_code.to_string()))
+}
+
+/// 行政区域コードが指定されたものに一致するFeatureを全て取得する（`dataset`内の対応する都道府県のデータを検索する）。
+/// 見つからない場合は空の列を返す。
+pub fn get_obj_has_specified_code(dataset: &Dataset, muni_code: &str) -> Result<Vec<GeoFeature>> {
+    let json_data =
+        read_municipalities_boundary_data(dataset, prefecture_name_from_code(muni_code)?)?;
+    Ok(json_data
+        .features
+        .into_iter()
+        .filter(|feat| geo_feature_props_to_code(&feat.properties).as_deref() == Some(muni_code))
+        .collect())
+}
+
+/// 自治体コードから自治体名を返す（データを検索して取得する）。
+/// 見つからない場合は`None`を返す。
+#[allow(unused)]
+pub fn get_muni_name_from_code(dataset: &Dataset, muni_code: &str) -> Result<Option<String>> {
+    Ok(get_obj_has_specified_code(dataset, muni_code)?
+        .first()
+        .map(|feat| geo_feature_props_to_name(&feat.properties)))
+}
+
+// { "type": "Feature", "properties": { "N03_001": "京都府", "N03_002": null, "N03_003": "与謝郡", "N03_004": "伊根町", "N03_007": "26463" }, "geometry": { "type": "Polygon", "coordinates": [ [ [ 135.233368689513554, 35.769235694272027 ], [ 135.233357664724622, 35.769233640220477 ], [ 135.233333332667371, 35.769238467781236 ], [ 135.233269026644393, 35.769251279523075 ], [ 135.233243060519044, 35.769259225033352 ], [ 135.233229493346585, 35.769265171350753 ], [ 135.233219584616336, 35.769273134847481 ], [ 135.233218339954647, 35.769276197938325 ], [ 135.233221970517661, 35.76928919853782 ], [ 135.233235446858544, 35.769302252197349 ], [ 135.23327852078728, 35.769296505529496 ], [ 135.233333332667371, 35.769274721251577 ], [ 135.233371025052975, 35.769259748438799 ], [ 135.233374720367237, 35.7692537481621 ], [ 135.233376056759766, 35.769242666715854 ], [ 135.233368689513554, 35.769235694272027 ] ] ] } },
+
+/// 穴や2つ目以降のポリゴン（飛び地など）の扱い
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PartHandling {
+    /// 黙って捨てる
+    #[default]
+    Drop,
+    /// 境界形状に含める
+    Keep,
+    /// 境界形状には含めず、捨てたことを報告する
+    Report,
+}
+
+impl FromStr for PartHandling {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "drop" => Ok(Self::Drop),
+            "keep" => Ok(Self::Keep),
+            "report" => Ok(Self::Report),
+            other => Err(Error::InvalidOption {
+                kind: "part handling",
+                value: other.to_string(),
+            }),
+        }
     }
-    Ok(result)
 }
 
-pub fn props_array_to_name(array: &[Option<String>; 7]) -> String {
-    // N03_001: 都道府県名
-    // N03_002: 支庁・振興局名（北海道のみ）
-    // N03_003: 郡・政令都市名
-    // N03_004: 市区町村名
-    // 5, 6は欠番
-    // N03_007: 行政区域コード
+/// 穴と2つ目以降のポリゴンそれぞれの扱いを指定する。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RingPolicy {
+    pub holes: PartHandling,
+    pub components: PartHandling,
+}
 
-    array
+impl RingPolicy {
+    /// 自治体の境界形状をまとめる際のデフォルト。
+    /// 2つ目以降のポリゴンは`MergeStrategy`でのまとめ方に任せ、穴は捨てる。
+    pub fn keep_components() -> Self {
+        Self {
+            holes: PartHandling::Drop,
+            components: PartHandling::Keep,
+        }
+    }
+}
+
+/// 報告対象となった部分の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartKind {
+    Hole,
+    Component,
+}
+
+/// `PartHandling::Report`により境界形状から除かれた部分。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReportedPart {
+    pub kind: PartKind,
+    /// ジオメトリ内で何番目のポリゴンに属するか
+    pub polygon_index: usize,
+    pub vertex_count: usize,
+}
+
+/// `RingPolicy`に従って取り出した環の列と報告。
+#[derive(Debug)]
+pub struct FeatureRings<'a> {
+    /// 含める外周。最初の要素は主となるポリゴンの外周
+    pub exteriors: Vec<&'a LinearRing>,
+    /// 含める穴
+    pub holes: Vec<&'a LinearRing>,
+    pub reported: Vec<ReportedPart>,
+}
+
+/// Featureのジオメトリから`policy`に従って環を取り出す。
+/// 外周の頂点数が最も多いポリゴンを主とし、その外周を最初に置く。ポリゴンが無い場合は`None`を返す。
+pub fn extract_rings<'a>(
+    geo_feature: &'a GeoFeature,
+    policy: RingPolicy,
+) -> Option<FeatureRings<'a>> {
+    let polygons = geo_feature.geometry.polygons();
+    let (main_idx, _) = polygons
         .iter()
-        .take(6)
         .enumerate()
-        .fold("".to_string(), |acc, (idx, v)| {
-            format!(
-                "{}{}",
-                acc,
-                match v {
-                    // 3番目が政令指定都市の場合4番目に含まれているので除外
-                    Some(v) if idx == 2 && ORDINANCE_DISIGNATED_CITIES.contains(&v.as_str()) =>
-                        "".to_string(),
-                    Some(v) => v.clone(),
-                    None => "".to_string(),
-                }
-            )
+        .max_by_key(|(_, p)| p.exterior.len())?;
+
+    let mut exteriors = vec![];
+    let mut holes = vec![];
+    let mut reported = vec![];
+    let mut handle = |handling, kind, polygon_index, ring: &'a LinearRing| match handling {
+        PartHandling::Drop => {}
+        PartHandling::Keep => match kind {
+            PartKind::Hole => holes.push(ring),
+            PartKind::Component => exteriors.push(ring),
+        },
+        PartHandling::Report => reported.push(ReportedPart {
+            kind,
+            polygon_index,
+            vertex_count: ring.len(),
+        }),
+    };
+    // 主となるポリゴンを先頭にして順に処理する
+    let order = std::iter::once(main_idx).chain((0..polygons.len()).filter(|&i| i != main_idx));
+    for polygon_index in order {
+        let polygon = &polygons[polygon_index];
+        if polygon_index != main_idx {
+            handle(
+                policy.components,
+                PartKind::Component,
+                polygon_index,
+                polygon.exterior,
+            );
+            // 含めないポリゴンの穴は、そのポリゴンとまとめて扱う
+            if policy.components != PartHandling::Keep {
+                continue;
+            }
+        } else {
+            handle(
+                PartHandling::Keep,
+                PartKind::Component,
+                polygon_index,
+                polygon.exterior,
+            );
+        }
+        for ring in polygon.interiors {
+            handle(policy.holes, PartKind::Hole, polygon_index, ring);
+        }
+    }
+    Some(FeatureRings {
+        exteriors,
+        holes,
+        reported,
+    })
+}
+
+/// 複数の環を周長に比例して点を配分しながら、環ごとに合計`sample_num`点程度にリサンプリングする。
+/// 別々の環の点をつなげると輪郭に不自然な飛びができるため、環ごとに閉じた点列とする。
+/// 環が一つの場合はちょうど`sample_num`点になる。
+pub fn resample_rings(
+    rings: &[Vec<Complex<f64>>],
+    sample_num: usize,
+    resampling: Resampling,
+) -> Result<Vec<ShapePoints>> {
+    if let [ring] = rings {
+        return Ok(vec![resampling.resample(ring, sample_num)?]);
+    }
+    if sample_num < 2 {
+        return Err(Error::InvalidSampleCount(sample_num));
+    }
+    let total_length = rings.iter().map(|r| perimeter(r)).sum::<f64>();
+    rings
+        .iter()
+        .map(|ring| {
+            // 小さな島でも形が残るよう最低限の点数は確保する
+            let num = (sample_num as f64 * perimeter(ring) / total_length).round() as usize;
+            resampling.resample(ring, num.max(MIN_CONTOUR_POINTS))
         })
+        .collect()
 }
 
-/// GISデータのプロパティから自治体名を返す。
-pub fn geo_feature_props_to_name(props: &HashMap<String, Option<String>>) -> String {
-    props_array_to_name(&geo_feature_props_to_array(props).unwrap())
+/// 境界の点列を指定された点の数にそろえる方法
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resampling {
+    /// 元の頂点を添字の上で等間隔に間引く、あるいは元の各辺に点を補間する
+    #[default]
+    Index,
+    /// 閉じた境界上に弧長で等間隔に点を置く
+    ArcLength,
+    /// 境界を弧長でパラメータ付けした曲線のフーリエ係数を辺ごとに解析的に求め、逆FFTで点を置く。
+    /// 点の数で表せない周波数を含まないので、弧長で等間隔に標本化したときの折り返しが生じない
+    Exact,
+}
+
+impl FromStr for Resampling {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "index" => Ok(Self::Index),
+            "arc" => Ok(Self::ArcLength),
+            "exact" => Ok(Self::Exact),
+            other => Err(Error::InvalidOption {
+                kind: "resampling method",
+                value: other.to_string(),
+            }),
+        }
+    }
 }
 
-// /// Featureの列から、`properties`が指定された自治体コードに一致するものを取得する。
-// /// 見つからない場合は`None`を返す。
-// pub fn get_obj_has_specified_code(muni_code: String) -> Option<GeoFeature> {}
+impl Resampling {
+    /// 点列を指定された点の数にそろえる。いずれの方法でも最初の点と最後の点が一致する。
+    /// 点の数が2未満の場合と、点列が一点に縮退している（異なる点を含まない）場合はエラーとする。
+    pub fn resample(
+        &self,
+        points: &[Complex<f64>],
+        result_points_num: usize,
+    ) -> Result<ShapePoints> {
+        if result_points_num < 2 {
+            return Err(Error::InvalidSampleCount(result_points_num));
+        }
+        if !points.iter().any(|p| *p != points[0]) {
+            return Err(Error::DegenerateRing {
+                vertices: points.len(),
+            });
+        }
+        Ok(match self {
+            Self::Index => resample_points(points, result_points_num),
+            Self::ArcLength => resample_arc_length(points, result_points_num),
+            Self::Exact => resample_exact(points, result_points_num),
+        })
+    }
+}
 
-// /// 自治体コードから自治体名を返す（データを検索して取得する）。
-// /// 見つからない場合は`None`を返す。
-// pub fn get_muni_name_from_code(muni_code: String) -> Option<String> {}
+/// 閉じた境界の係数を`Spectrum::from_polygon`で求め、一周を`result_points_num - 1`等分した位置の点を再構成する。
+/// 最後の点は最初の点と一致させる。高い周波数を落とすので、点は元の頂点や辺の上を通るとは限らない。
+fn resample_exact(points: &[Complex<f64>], result_points_num: usize) -> ShapePoints {
+    let len = result_points_num - 1;
+    let mut result =
+        Spectrum::from_polygon(points, len, FftNormalization::ByLength).reconstruct(len);
+    result.push(result[0]);
+    result
+}
 
-// { "type": "Feature", "properties": { "N03_001": "京都府", "N03_002": null, "N03_003": "与謝郡", "N03_004": "伊根町", "N03_007": "26463" }, "geometry": { "type": "Polygon", "coordinates": [ [ [ 135.233368689513554, 35.769235694272027 ], [ 135.233357664724622, 35.769233640220477 ], [ 135.233333332667371, 35.769238467781236 ], [ 135.233269026644393, 35.769251279523075 ], [ 135.233243060519044, 35.769259225033352 ], [ 135.233229493346585, 35.769265171350753 ], [ 135.233219584616336, 35.769273134847481 ], [ 135.233218339954647, 35.769276197938325 ], [ 135.233221970517661, 35.76928919853782 ], [ 135.233235446858544, 35.769302252197349 ], [ 135.23327852078728, 35.769296505529496 ], [ 135.233333332667371, 35.769274721251577 ], [ 135.233371025052975, 35.769259748438799 ], [ 135.233374720367237, 35.7692537481621 ], [ 135.233376056759766, 35.769242666715854 ], [ 135.233368689513554, 35.769235694272027 ] ] ] } },
+/// 閉じた境界上に弧長で等間隔に点を置く。最初の点は元の最初の点とし、最後の点はそれと一致させる。
+/// 点列が閉じていない場合は最後の点から最初の点への辺を補う。
+pub fn resample_arc_length(points: &[Complex<f64>], result_points_num: usize) -> ShapePoints {
+    let mut ring = points.to_vec();
+    if let (Some(&first), Some(&last)) = (ring.first(), ring.last()) {
+        if first != last {
+            ring.push(first);
+        }
+    }
+    // 各頂点までの累積弧長
+    let mut cumulative = vec![0.0];
+    for pair in ring.windows(2) {
+        cumulative.push(cumulative.last().unwrap() + (pair[1] - pair[0]).norm());
+    }
+    let total_length = *cumulative.last().unwrap();
+    if result_points_num < 2 || total_length == 0.0 {
+        return vec![ring[0]; result_points_num];
+    }
+
+    let mut results = Vec::with_capacity(result_points_num);
+    let mut segment_idx = 0usize;
+    for idx in 0..result_points_num - 1 {
+        let target = total_length * idx as f64 / (result_points_num - 1) as f64;
+        while cumulative[segment_idx + 1] < target {
+            segment_idx += 1;
+        }
+        let segment_length = cumulative[segment_idx + 1] - cumulative[segment_idx];
+        let rate = if segment_length > 0.0 {
+            (target - cumulative[segment_idx]) / segment_length
+        } else {
+            0.0
+        };
+        results.push(ring[segment_idx] + (ring[segment_idx + 1] - ring[segment_idx]) * rate);
+    }
+    results.push(ring[0]);
+    results
+}
 
-/// 自治体GISデータのFeatureデータから境界形状の点列を取得する。
-/// 点の数を指定して間引くあるいは補間（中心補間）し、必ず最初の点と最後の点が一致するようにする。
-pub fn convert_to_shape(geo_feature: &GeoFeature, result_points_num: usize) -> ShapePoints {
-    // なぜか元データにおいて一回分Vec階層が多いので[0]を取得する必要がある
-    let coordinates = &geo_feature.geometry.coordinates[0];
-    let coord_data_num = coordinates.len();
-    println!("raw_data_num: {}", coord_data_num);
-    if coord_data_num >= result_points_num {
+/// 点列を指定された点の数に間引くあるいは補間（中心補間）し、必ず最初の点と最後の点が一致するようにする。
+/// 点の数と点列は`Resampling::resample`で検査済みのものとする。
+fn resample_points(points: &[Complex<f64>], result_points_num: usize) -> ShapePoints {
+    let points_num = points.len();
+    if points_num >= result_points_num {
         // 間引く場合、最初と最後を保ちつつなるべく等間隔に間引く
         (0..result_points_num)
-            .map(|idx| {
-                let pick_idx = (coord_data_num - 1) * idx / (result_points_num - 1);
-                let p_vec = &coordinates[pick_idx];
-                Complex::new(p_vec[0], p_vec[1])
-            })
+            .map(|idx| points[(points_num - 1) * idx / (result_points_num - 1)])
             .collect::<Vec<_>>()
     } else {
         // 挿入する場合、元の点列を保ちつつ適当な数の点を挿入していく
-        let points = coordinates
-            .iter()
-            .map(|p_vec| Complex::new(p_vec[0], p_vec[1]))
-            .collect::<Vec<_>>();
-        let points_num = coord_data_num;
         let mut results = vec![points[0]];
         let mut prev_idx_in_results = 0usize;
         let mut prev_p = points[0];
@@ -0,0 +1,271 @@
+//! 読み込んだ境界の環の向きや重複頂点を検査し、正規化する
+
+use rustfft::num_complex::Complex;
+use serde::Serialize;
+
+use super::serde_models::{GeoFeature, LinearRing};
+use crate::geometry::signed_area;
+
+/// 環に加えた修正
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub enum RingFix {
+    /// 向きを反転した（外周は反時計回り、穴は時計回りにそろえる）
+    Reoriented,
+    /// 連続する重複頂点を取り除いた
+    DuplicatesRemoved { count: usize },
+    /// 最後に最初の点を加えて閉じた
+    Closed,
+    /// 座標が2つ未満の位置を取り除いた
+    InvalidPositionsRemoved { count: usize },
+}
+
+/// 一つの環の検査結果
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RingReport {
+    /// ジオメトリ内で何番目のポリゴンに属するか
+    pub polygon_index: usize,
+    /// ポリゴン内で何番目の環か（0が外周、1以降が穴）
+    pub ring_index: usize,
+    /// 読み込んだ時点で閉じていたかどうか
+    pub was_closed: bool,
+    pub fixes: Vec<RingFix>,
+    /// 自己交差（隣り合わない辺どうしの交差や接触）する辺の組。辺`i`は頂点`i`から`i + 1`へ向かう
+    pub self_intersections: Vec<(usize, usize)>,
+    /// 正規化した後も面を成さない（頂点が3つ未満あるいは面積が0）かどうか
+    pub degenerate: bool,
+}
+
+impl RingReport {
+    /// 修正も問題も無いかどうかを返す。
+    pub fn is_clean(&self) -> bool {
+        self.fixes.is_empty() && self.self_intersections.is_empty() && !self.degenerate
+    }
+}
+
+/// Featureの全ての環を正規化し、環ごとの検査結果を返す。
+/// 外周を反時計回り、穴を時計回りにそろえ、連続する重複頂点を取り除き、閉じていない環を閉じる。
+/// 自己交差は検出するのみで修正しない。
+pub fn normalize_feature(feature: &mut GeoFeature) -> Vec<RingReport> {
+    let mut reports = vec![];
+    for (polygon_index, polygon) in feature.geometry.polygons_mut().into_iter().enumerate() {
+        for (ring_index, ring) in polygon.iter_mut().enumerate() {
+            reports.push(normalize_ring(ring, polygon_index, ring_index));
+        }
+    }
+    reports
+}
+
+/// Featureの全ての環を`normalize_feature`と同じく正規化する。検査結果は作らず、自己交差も調べない。
+/// 境界データを読み込む際に適用する。
+pub fn normalize_geometry(feature: &mut GeoFeature) {
+    for polygon in feature.geometry.polygons_mut() {
+        for (ring_index, ring) in polygon.iter_mut().enumerate() {
+            fix_ring(ring, ring_index);
+        }
+    }
+}
+
+/// 一つの環を正規化し、検査結果を返す。`ring_index`が0なら外周として扱う。
+fn normalize_ring(ring: &mut LinearRing, polygon_index: usize, ring_index: usize) -> RingReport {
+    let (was_closed, fixes, area) = fix_ring(ring, ring_index);
+    let open_len = ring.len().saturating_sub(1);
+    RingReport {
+        polygon_index,
+        ring_index,
+        was_closed,
+        fixes,
+        self_intersections: self_intersections(&to_points(ring)),
+        degenerate: open_len < 3 || area == 0.0,
+    }
+}
+
+fn to_points(ring: &LinearRing) -> Vec<Complex<f64>> {
+    ring.iter().map(|p| Complex::new(p[0], p[1])).collect()
+}
+
+/// 一つの環を正規化し、読み込んだ時点で閉じていたかどうか・加えた修正・符号付き面積を返す。
+/// 座標が2つ未満の位置は他の処理の前に取り除く。
+fn fix_ring(ring: &mut LinearRing, ring_index: usize) -> (bool, Vec<RingFix>, f64) {
+    let mut fixes = vec![];
+    let original_len = ring.len();
+    ring.retain(|p| p.len() >= 2);
+    if ring.len() != original_len {
+        fixes.push(RingFix::InvalidPositionsRemoved {
+            count: original_len - ring.len(),
+        });
+    }
+    let was_closed = ring.len() >= 2 && ring.first() == ring.last();
+
+    let original_len = ring.len();
+    ring.dedup_by(|a, b| a[..2] == b[..2]);
+    if ring.len() != original_len {
+        fixes.push(RingFix::DuplicatesRemoved {
+            count: original_len - ring.len(),
+        });
+    }
+    if !was_closed && !ring.is_empty() {
+        ring.push(ring[0].clone());
+        fixes.push(RingFix::Closed);
+    }
+
+    let area = signed_area(&to_points(ring));
+    if (ring_index == 0 && area < 0.0) || (ring_index > 0 && area > 0.0) {
+        ring.reverse();
+        fixes.push(RingFix::Reoriented);
+    }
+    (was_closed, fixes, area)
+}
+
+/// 閉じた環の隣り合わない辺どうしが交差あるいは接触する組を返す。
+/// 辺をx座標の範囲で並べ、範囲が重なるものだけを調べる。
+fn self_intersections(ring: &[Complex<f64>]) -> Vec<(usize, usize)> {
+    let n = ring.len().saturating_sub(1);
+    if n < 4 {
+        return vec![];
+    }
+    let segment = |i: usize| (ring[i], ring[i + 1]);
+    let min_x = |i: usize| ring[i].re.min(ring[i + 1].re);
+    let max_x = |i: usize| ring[i].re.max(ring[i + 1].re);
+    let mut order = (0..n).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| min_x(a).total_cmp(&min_x(b)));
+
+    let mut result = vec![];
+    let mut active: Vec<usize> = vec![];
+    for &i in order.iter() {
+        active.retain(|&j| max_x(j) >= min_x(i));
+        for &j in active.iter() {
+            let (a, b) = (i.min(j), i.max(j));
+            let adjacent = b - a == 1 || (a == 0 && b == n - 1);
+            if !adjacent && segments_intersect(segment(a), segment(b)) {
+                result.push((a, b));
+            }
+        }
+        active.push(i);
+    }
+    result.sort();
+    result
+}
+
+/// 二つの線分が共有点を持つかどうかを返す。
+fn segments_intersect(
+    (p1, p2): (Complex<f64>, Complex<f64>),
+    (q1, q2): (Complex<f64>, Complex<f64>),
+) -> bool {
+    let cross = |o: Complex<f64>, a: Complex<f64>, b: Complex<f64>| {
+        (a - o).re * (b - o).im - (a - o).im * (b - o).re
+    };
+    let on_segment = |a: Complex<f64>, b: Complex<f64>, p: Complex<f64>| {
+        p.re >= a.re.min(b.re)
+            && p.re <= a.re.max(b.re)
+            && p.im >= a.im.min(b.im)
+            && p.im <= a.im.max(b.im)
+    };
+    let (d1, d2) = (cross(q1, q2, p1), cross(q1, q2, p2));
+    let (d3, d4) = (cross(p1, p2, q1), cross(p1, p2, q2));
+    if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+        return true;
+    }
+    (d1 == 0.0 && on_segment(q1, q2, p1))
+        || (d2 == 0.0 && on_segment(q1, q2, p2))
+        || (d3 == 0.0 && on_segment(p1, p2, q1))
+        || (d4 == 0.0 && on_segment(p1, p2, q2))
+}
+
+/// 向き・重複頂点・閉じているかどうかの修正と、自己交差の検出を確かめる。
+#[test]
+fn test_normalize_feature() {
+    use std::collections::HashMap;
+
+    use super::serde_models::FeatureGeometry;
+
+    // 時計回りで重複頂点を含み閉じていない外周と、反時計回りの穴
+    let exterior = vec![
+        vec![0.0, 0.0],
+        vec![0.0, 4.0],
+        vec![0.0, 4.0],
+        vec![4.0, 4.0],
+        vec![4.0, 0.0],
+    ];
+    let hole = vec![
+        vec![1.0, 1.0],
+        vec![2.0, 1.0],
+        vec![2.0, 2.0],
+        vec![1.0, 1.0],
+    ];
+    // 8の字に自己交差する外周
+    let bowtie = vec![
+        vec![10.0, 0.0],
+        vec![11.0, 1.0],
+        vec![11.0, 0.0],
+        vec![10.0, 1.0],
+        vec![10.0, 0.0],
+    ];
+    let mut feature = GeoFeature {
+        _type: "Feature".to_string(),
+        properties: HashMap::new(),
+        geometry: FeatureGeometry::MultiPolygon {
+            coordinates: vec![vec![exterior, hole], vec![bowtie]],
+        },
+    };
+    let reports = normalize_feature(&mut feature);
+    assert_eq!(reports.len(), 3);
+
+    assert!(!reports[0].was_closed);
+    assert_eq!(
+        reports[0].fixes,
+        vec![
+            RingFix::DuplicatesRemoved { count: 1 },
+            RingFix::Closed,
+            RingFix::Reoriented
+        ]
+    );
+    assert!(reports[0].self_intersections.is_empty());
+    assert_eq!(reports[1].fixes, vec![RingFix::Reoriented]);
+    assert_eq!(reports[2].self_intersections, vec![(0, 2)]);
+
+    let polygons = feature.geometry.polygons();
+    let to_points = |ring: &LinearRing| {
+        ring.iter()
+            .map(|p| Complex::new(p[0], p[1]))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(signed_area(&to_points(polygons[0].exterior)), 16.0);
+    assert_eq!(signed_area(&to_points(&polygons[0].interiors[0])), -0.5);
+    assert_eq!(polygons[0].exterior.len(), 5);
+    assert_eq!(polygons[0].exterior.first(), polygons[0].exterior.last());
+
+    // 正規化済みのものは何も修正しない
+    let reports = normalize_feature(&mut feature);
+    assert!(reports[0].is_clean() && reports[1].is_clean());
+    assert!(!reports[2].is_clean());
+
+    // 座標が2つ未満の位置は取り除き、読み込み時の正規化でも同じ結果になる
+    let broken = vec![
+        vec![0.0, 0.0],
+        vec![1.0],
+        vec![1.0, 0.0],
+        vec![],
+        vec![1.0, 1.0],
+        vec![0.0, 0.0],
+    ];
+    let mut feature = GeoFeature {
+        _type: "Feature".to_string(),
+        properties: HashMap::new(),
+        geometry: FeatureGeometry::Polygon {
+            coordinates: vec![broken],
+        },
+    };
+    let mut loaded = feature.clone();
+    let reports = normalize_feature(&mut feature);
+    assert_eq!(
+        reports[0].fixes,
+        vec![RingFix::InvalidPositionsRemoved { count: 2 }]
+    );
+    assert!(!reports[0].degenerate);
+    normalize_geometry(&mut loaded);
+    assert_eq!(loaded.geometry.polygons()[0].exterior.len(), 4);
+    assert_eq!(
+        feature.geometry.polygons()[0].exterior,
+        loaded.geometry.polygons()[0].exterior
+    );
+}
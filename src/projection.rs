@@ -0,0 +1,223 @@
+//! 経度・緯度を平面座標（メートル）に投影する
+
+use std::str::FromStr;
+
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// GRS80楕円体の長半径
+const GRS80_A: f64 = 6378137.0;
+/// GRS80楕円体の逆扁平率
+const GRS80_F_INV: f64 = 298.257222101;
+/// 平面直角座標系の縮尺係数
+const PLANE_RECTANGULAR_SCALE: f64 = 0.9999;
+
+/// 平面直角座標系の各系の原点（緯度, 経度）。系番号1〜19の順に並ぶ。
+const PLANE_RECTANGULAR_ORIGINS: [(f64, f64); 19] = [
+    (33.0, 129.0 + 30.0 / 60.0),
+    (33.0, 131.0),
+    (36.0, 132.0 + 10.0 / 60.0),
+    (33.0, 133.0 + 30.0 / 60.0),
+    (36.0, 134.0 + 20.0 / 60.0),
+    (36.0, 136.0),
+    (36.0, 137.0 + 10.0 / 60.0),
+    (36.0, 138.0 + 30.0 / 60.0),
+    (36.0, 139.0 + 50.0 / 60.0),
+    (40.0, 140.0 + 50.0 / 60.0),
+    (44.0, 140.0 + 15.0 / 60.0),
+    (44.0, 142.0 + 15.0 / 60.0),
+    (44.0, 144.0 + 15.0 / 60.0),
+    (26.0, 142.0),
+    (26.0, 127.0 + 30.0 / 60.0),
+    (26.0, 124.0),
+    (26.0, 131.0),
+    (20.0, 136.0),
+    (26.0, 154.0),
+];
+
+/// 経度・緯度から平面座標への投影方法。
+/// 入力の複素数は実部を経度、虚部を緯度（いずれも度）とする。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Projection {
+    /// 経度・緯度をそのまま平面座標とみなす（投影しない）
+    LonLat,
+    /// 全頂点の重心を中心とする局所的な正距円筒図法
+    #[default]
+    LocalEquirectangular,
+    /// 平面直角座標系（系番号1〜19）
+    PlaneRectangular(u8),
+}
+
+impl FromStr for Projection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "lonlat" => Ok(Self::LonLat),
+            "local" => Ok(Self::LocalEquirectangular),
+            _ => match s.strip_prefix("jprcs").map(|zone| zone.parse::<u8>()) {
+                Some(Ok(zone)) if (1..=19).contains(&zone) => Ok(Self::PlaneRectangular(zone)),
+                _ => Err(Error::InvalidOption {
+                    kind: "projection",
+                    value: s.to_string(),
+                }),
+            },
+        }
+    }
+}
+
+impl Projection {
+    /// 環の列をまとめて投影する。局所的な投影の中心は全ての環の頂点から求める。
+    pub fn project(&self, rings: &[Vec<Complex<f64>>]) -> Result<Vec<Vec<Complex<f64>>>> {
+        let project_point: Box<dyn Fn(Complex<f64>) -> Complex<f64>> = match *self {
+            Self::LonLat => return Ok(rings.to_vec()),
+            Self::LocalEquirectangular => {
+                let count = rings.iter().map(|r| r.len()).sum::<usize>();
+                if count == 0 {
+                    return Ok(rings.to_vec());
+                }
+                let center = rings.iter().flatten().sum::<Complex<f64>>() / count as f64;
+                Box::new(move |p| local_equirectangular(p, center))
+            }
+            Self::PlaneRectangular(zone) => {
+                let Some(&(lat0, lon0)) = (zone as usize)
+                    .checked_sub(1)
+                    .and_then(|idx| PLANE_RECTANGULAR_ORIGINS.get(idx))
+                else {
+                    return Err(Error::InvalidOption {
+                        kind: "plane rectangular zone (1..=19)",
+                        value: zone.to_string(),
+                    });
+                };
+                let meridian_arc0 = TransverseMercator::new().meridian_arc(lat0.to_radians());
+                Box::new(move |p| {
+                    TransverseMercator::new().project(p, Complex::new(lon0, lat0), meridian_arc0)
+                })
+            }
+        };
+        Ok(rings
+            .iter()
+            .map(|ring| ring.iter().map(|&p| project_point(p)).collect())
+            .collect())
+    }
+}
+
+/// `center`を中心とする正距円筒図法で投影する。GRS80の長半径を半径とする球で近似する。
+fn local_equirectangular(p: Complex<f64>, center: Complex<f64>) -> Complex<f64> {
+    let d = (p - center) * std::f64::consts::PI / 180.0 * GRS80_A;
+    Complex::new(d.re * center.im.to_radians().cos(), d.im)
+}
+
+/// GRS80楕円体上の横メルカトル図法（ガウス・クリューゲル図法）。
+/// 国土地理院の公開している計算式（河瀬, 2011）による。
+struct TransverseMercator {
+    /// 第三扁平率
+    n: f64,
+}
+
+impl TransverseMercator {
+    fn new() -> Self {
+        Self {
+            n: 1.0 / (2.0 * GRS80_F_INV - 1.0),
+        }
+    }
+
+    /// 縮尺係数を掛けた定数`A̅`
+    fn scaled_radius(&self) -> f64 {
+        let n = self.n;
+        PLANE_RECTANGULAR_SCALE * GRS80_A / (1.0 + n) * (1.0 + n.powi(2) / 4.0 + n.powi(4) / 64.0)
+    }
+
+    /// 赤道から緯度`phi`（ラジアン）までの子午線弧長に縮尺係数を掛けたもの
+    fn meridian_arc(&self, phi: f64) -> f64 {
+        let n = self.n;
+        let coefficients = [
+            1.0 + n.powi(2) / 4.0 + n.powi(4) / 64.0,
+            -3.0 / 2.0 * (n - n.powi(3) / 8.0 - n.powi(5) / 64.0),
+            15.0 / 16.0 * (n.powi(2) - n.powi(4) / 4.0),
+            -35.0 / 48.0 * (n.powi(3) - 5.0 / 16.0 * n.powi(5)),
+            315.0 / 512.0 * n.powi(4),
+            -693.0 / 1280.0 * n.powi(5),
+        ];
+        let series = coefficients[0] * phi
+            + coefficients
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(|(j, a)| a * (2.0 * j as f64 * phi).sin())
+                .sum::<f64>();
+        PLANE_RECTANGULAR_SCALE * GRS80_A / (1.0 + n) * series
+    }
+
+    /// 経度・緯度`p`を原点`origin`の座標系に投影し、（東向き, 北向き）の複素数で返す。
+    fn project(&self, p: Complex<f64>, origin: Complex<f64>, meridian_arc0: f64) -> Complex<f64> {
+        let n = self.n;
+        let alpha = [
+            n / 2.0 - 2.0 / 3.0 * n.powi(2) + 5.0 / 16.0 * n.powi(3),
+            13.0 / 48.0 * n.powi(2) - 3.0 / 5.0 * n.powi(3),
+            61.0 / 240.0 * n.powi(3),
+        ];
+        let phi = p.im.to_radians();
+        let d_lambda = (p.re - origin.re).to_radians();
+        let e = 2.0 * n.sqrt() / (1.0 + n);
+        let t = (phi.sin().atanh() - e * (e * phi.sin()).atanh()).sinh();
+        let t_bar = (1.0 + t * t).sqrt();
+        let xi = t.atan2(d_lambda.cos());
+        let eta = (d_lambda.sin() / t_bar).atanh();
+
+        let (mut northing, mut easting) = (xi, eta);
+        for (j, a) in alpha.iter().enumerate() {
+            let k = 2.0 * (j + 1) as f64;
+            northing += a * (k * xi).sin() * (k * eta).cosh();
+            easting += a * (k * xi).cos() * (k * eta).sinh();
+        }
+        let radius = self.scaled_radius();
+        Complex::new(radius * easting, radius * northing - meridian_arc0)
+    }
+}
+
+/// 投影の基本的な性質を確かめる。
+#[test]
+fn test_projection() {
+    // 原点は(0, 0)に写る（級数の打ち切りによる誤差はmm未満）
+    let origin = Complex::new(139.0 + 50.0 / 60.0, 36.0);
+    let projected = Projection::PlaneRectangular(9)
+        .project(&[vec![origin]])
+        .unwrap();
+    assert!(projected[0][0].norm() < 1e-3);
+
+    // 原点の近くでは局所的な正距円筒図法とほぼ一致する
+    let p = Complex::new(140.0 + 5.0 / 60.0, 36.0 + 6.0 / 60.0);
+    let projected = Projection::PlaneRectangular(9).project(&[vec![p]]).unwrap()[0][0];
+    let local = local_equirectangular(p, origin);
+    assert!((projected - local).norm() / local.norm() < 1e-2);
+
+    // 北海道付近の経緯度で正方形のものは、東西方向に縮む
+    let square = [(0.0, 0.0), (0.1, 0.0), (0.1, 0.1), (0.0, 0.1)]
+        .into_iter()
+        .map(|(x, y)| Complex::new(142.0 + x, 45.0 + y))
+        .collect::<Vec<_>>();
+    for projection in [
+        Projection::LocalEquirectangular,
+        Projection::PlaneRectangular(12),
+    ] {
+        let projected = &projection.project(std::slice::from_ref(&square)).unwrap()[0];
+        let width = (projected[1] - projected[0]).norm();
+        let height = (projected[3] - projected[0]).norm();
+        assert!((width / height - 45.05f64.to_radians().cos()).abs() < 1e-2);
+    }
+    assert_eq!(
+        Projection::LonLat
+            .project(std::slice::from_ref(&square))
+            .unwrap()[0],
+        square
+    );
+
+    assert_eq!(
+        "jprcs9".parse::<Projection>().unwrap(),
+        Projection::PlaneRectangular(9)
+    );
+    assert!("jprcs20".parse::<Projection>().is_err());
+}
@@ -0,0 +1,143 @@
+//! 全国の自治体から形状の似ている自治体を探す
+
+use crate::{
+    error::{Error, Result},
+    municipalities::{data::PREFECTURES, dataset::Dataset},
+    shapes::{prefecture_municipality_shapes, prefecture_shape, ShapeOptions},
+    similarity::{FourierDescriptor, DESCRIPTOR_HARMONICS},
+};
+
+/// 記述子の列から、`query`に近いものを距離の昇順に`k`個返す。
+pub fn nearest_neighbours(
+    query: &FourierDescriptor,
+    candidates: &[(String, FourierDescriptor)],
+    k: usize,
+) -> Vec<(String, f64)> {
+    let mut scored = candidates
+        .iter()
+        .map(|(name, descriptor)| (name.clone(), query.distance(descriptor)))
+        .collect::<Vec<_>>();
+    scored.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+    scored.truncate(k);
+    scored
+}
+
+/// `dataset`の全都道府県の自治体について記述子を計算する。
+/// 各都道府県のファイルは一度だけ読み込む。
+pub fn all_municipality_descriptors(
+    dataset: &Dataset,
+    options: &ShapeOptions,
+) -> Result<Vec<(String, FourierDescriptor)>> {
+    let mut descriptors = vec![];
+    for prefecture_name in PREFECTURES.iter().skip(1) {
+        let shapes = prefecture_municipality_shapes(dataset, prefecture_name, options)?;
+        descriptors.extend(shapes.into_iter().map(|shape| {
+            let descriptor = FourierDescriptor::new(&shape.outline(), DESCRIPTOR_HARMONICS);
+            (shape.name, descriptor)
+        }));
+    }
+    Ok(descriptors)
+}
+
+/// `dataset`の全都道府県について、都道府県全体を合併した形状の記述子を計算する。
+pub fn all_prefecture_descriptors(
+    dataset: &Dataset,
+    options: &ShapeOptions,
+) -> Result<Vec<(String, FourierDescriptor)>> {
+    PREFECTURES
+        .iter()
+        .skip(1)
+        .map(|&prefecture_name| {
+            let shape = prefecture_shape(dataset, prefecture_name, options)?;
+            let descriptor = FourierDescriptor::new(&shape.outline(), DESCRIPTOR_HARMONICS);
+            Ok((shape.name, descriptor))
+        })
+        .collect()
+}
+
+/// 都道府県内の自治体のうち、都道府県全体の形状に似ているもの（都道府県の縮図）を`k`個、距離とともに返す。
+pub fn prefecture_miniatures(
+    dataset: &Dataset,
+    prefecture_name: &str,
+    k: usize,
+    options: &ShapeOptions,
+) -> Result<Vec<(String, f64)>> {
+    let prefecture = prefecture_shape(dataset, prefecture_name, options)?;
+    let query = FourierDescriptor::new(&prefecture.outline(), DESCRIPTOR_HARMONICS);
+    let candidates = prefecture_municipality_shapes(dataset, prefecture_name, options)?
+        .into_iter()
+        .map(|shape| {
+            let descriptor = FourierDescriptor::new(&shape.outline(), DESCRIPTOR_HARMONICS);
+            (shape.name, descriptor)
+        })
+        .collect::<Vec<_>>();
+    Ok(nearest_neighbours(&query, &candidates, k))
+}
+
+/// 自治体名をGISデータ内の完全名で与え、全国で形状が最も似ている自治体を`k`個、距離とともに返す。
+/// 検索対象の自治体自身は結果に含めない。
+pub fn search_similar_municipalities(
+    dataset: &Dataset,
+    muni_name: &str,
+    k: usize,
+    options: &ShapeOptions,
+) -> Result<Vec<(String, f64)>> {
+    let descriptors = all_municipality_descriptors(dataset, options)?;
+    search_in_descriptors(&descriptors, muni_name, k)
+}
+
+/// 名前付き記述子の列の中から`muni_name`の記述子を探し、それに近いものを`k`個返す。
+/// 検索対象の自治体自身は結果に含めない。
+pub fn search_in_descriptors(
+    descriptors: &[(String, FourierDescriptor)],
+    muni_name: &str,
+    k: usize,
+) -> Result<Vec<(String, f64)>> {
+    let query = descriptors
+        .iter()
+        .find(|(name, _)| name == muni_name)
+        .map(|(_, descriptor)| descriptor.clone())
+        .ok_or_else(|| Error::MunicipalityNotFound {
+            name: muni_name.to_string(),
+            location: "descriptors".to_string(),
+        })?;
+    let candidates = descriptors
+        .iter()
+        .filter(|(name, _)| name != muni_name)
+        .cloned()
+        .collect::<Vec<_>>();
+    Ok(nearest_neighbours(&query, &candidates, k))
+}
+
+/// 近傍探索が距離の昇順に指定数だけ返すことを確かめる。
+#[test]
+fn test_nearest_neighbours() {
+    use rustfft::num_complex::Complex;
+
+    use crate::shapes::{flower, rectangle, simple_circle, ShapePoints};
+
+    let rotated_flower = flower()
+        .iter()
+        .map(|c| c * Complex::cis(0.5) * 3.0)
+        .collect::<ShapePoints>();
+    let candidates = [
+        ("rectangle", rectangle()),
+        ("circle", simple_circle()),
+        ("rotated_flower", rotated_flower),
+    ]
+    .into_iter()
+    .map(|(name, shape)| {
+        (
+            name.to_string(),
+            FourierDescriptor::new(&shape, DESCRIPTOR_HARMONICS),
+        )
+    })
+    .collect::<Vec<_>>();
+    let query = FourierDescriptor::new(&flower(), DESCRIPTOR_HARMONICS);
+
+    let result = nearest_neighbours(&query, &candidates, 2);
+    assert_eq!(result.len(), 2);
+    assert_eq!(result[0].0, "rotated_flower");
+    assert!(result[0].1 < 1e-10);
+    assert!(result[0].1 <= result[1].1);
+}
@@ -1,11 +1,28 @@
-use std::f64::consts::TAU;
+use std::{collections::HashMap, f64::consts::TAU, str::FromStr};
 
-use regex::Regex;
 use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    error::{Error, Result},
+    geometry::{
+        bridge_rings, contains_point, convex_hull, counter_clockwise, dissolve, signed_area,
+    },
     io::read_municipalities_boundary_data,
-    municipalities::utils::{convert_to_shape, geo_feature_props_to_name, normalize_shape},
+    municipalities::{
+        data::{PREFECTURES, REGIONS},
+        dataset::Dataset,
+        mapping::PropertyMapping,
+        serde_models::{GeoFeature, LinearRing},
+        utils::{
+            extract_rings, geo_feature_props_to_area_name, geo_feature_props_to_code,
+            geo_feature_props_to_name, get_obj_has_specified_code, is_muni_code, normalize_shape,
+            prefecture_name_from_code, resample_rings, AreaLevel, ReportedPart, Resampling,
+            RingPolicy,
+        },
+    },
+    projection::Projection,
+    simplify::{Simplification, SimplificationReport},
 };
 
 pub type ShapePoints = Vec<Complex<f64>>;
@@ -51,8 +68,125 @@ pub fn flower() -> ShapePoints {
     points
 }
 
-/// 自治体名をGISデータ内の完全名で与え、境界形状点列データを取得する。
-pub fn municipality_shape(muni_name: &str, result_point_num: usize) -> ShapePoints {
+/// 自治体を構成する複数のfeature（飛び地や島）をどのように一つの形状にまとめるか
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergeStrategy {
+    /// 面積が最大のポリゴンの外周のみを使う
+    #[default]
+    LargestArea,
+    /// 全ての部分を合併し、離れた部分は幅0の橋でつないで一つの輪郭にする
+    Union,
+    /// 全ての部分の凸包を使う
+    ConvexHull,
+    /// 合併した各部分を別々の輪郭として保持する
+    MultiContour,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "largest" => Ok(Self::LargestArea),
+            "union" => Ok(Self::Union),
+            "hull" => Ok(Self::ConvexHull),
+            "multi" => Ok(Self::MultiContour),
+            other => Err(Error::InvalidOption {
+                kind: "merge strategy",
+                value: other.to_string(),
+            }),
+        }
+    }
+}
+
+/// 自治体の境界形状を取得する際の設定
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ShapeOptions {
+    /// 結果の点の数
+    pub sample_num: usize,
+    pub strategy: MergeStrategy,
+    /// リサンプリングの前に適用する投影
+    pub projection: Projection,
+    pub resampling: Resampling,
+    /// 都道府県内の全自治体を取得する際に、この単位の区域に属する自治体をまとめて一つの形状とする
+    #[serde(default)]
+    pub aggregate: Option<AreaLevel>,
+    /// リサンプリングの前に適用する単純化
+    #[serde(default)]
+    pub simplification: Simplification,
+    /// 各featureの穴と2つ目以降のポリゴンの扱い
+    #[serde(default = "RingPolicy::keep_components")]
+    pub rings: RingPolicy,
+    /// 単純化の結果に単純化前後のハウスドルフ距離を含めるかどうか。
+    /// 頂点数の積に比例する時間がかかるので、結果を表示する場合だけ有効にする。形状には影響しないので保存しない。
+    #[serde(skip)]
+    pub measure_simplification: bool,
+}
+
+impl ShapeOptions {
+    /// 点の数を指定し、それ以外はデフォルトの設定とする。
+    pub fn new(sample_num: usize) -> Self {
+        Self {
+            sample_num,
+            strategy: MergeStrategy::default(),
+            projection: Projection::default(),
+            resampling: Resampling::default(),
+            aggregate: None,
+            simplification: Simplification::default(),
+            rings: RingPolicy::keep_components(),
+            measure_simplification: false,
+        }
+    }
+}
+
+/// 自治体の境界形状。どの方法でまとめたかを併せて保持する。
+#[derive(Debug, Clone)]
+pub struct MunicipalityShape {
+    pub name: String,
+    /// 行政区域コード（所属未定地などコードを持たないものは`None`）
+    pub code: Option<String>,
+    #[allow(unused)]
+    pub strategy: MergeStrategy,
+    /// 正規化済みの輪郭。`MergeStrategy::MultiContour`以外では要素は一つ。
+    pub contours: Vec<ShapePoints>,
+    /// リサンプリング前の単純化の結果（全ての輪郭をまとめたもの）
+    pub simplification: SimplificationReport,
+    /// `PartHandling::Report`により形状から除いた穴やポリゴン
+    pub reported: Vec<ReportedPart>,
+}
+
+impl MunicipalityShape {
+    /// 全ての輪郭をつなげた一つの点列を返す。
+    pub fn outline(&self) -> ShapePoints {
+        self.contours.concat()
+    }
+}
+
+/// 自治体名をGISデータ内の完全名あるいは5桁の行政区域コードで与え、`dataset`から境界形状点列データを取得する。
+pub fn municipality_shape(
+    dataset: &Dataset,
+    muni_name: &str,
+    result_point_num: usize,
+) -> Result<ShapePoints> {
+    let options = ShapeOptions::new(result_point_num);
+    Ok(municipality_shape_with_options(dataset, muni_name, &options)?.outline())
+}
+
+/// 自治体名をGISデータ内の完全名あるいは5桁の行政区域コードで与え、`dataset`から設定に従って境界形状を取得する。
+/// 自治体名の代わりに政令指定都市・郡・振興局の区域名（例: "兵庫県神戸市"）を与えた場合は、
+/// 区域内の全自治体を合併した形状を返す。都道府県名や地方名を与えた場合も同様に合併した形状を返す。
+pub fn municipality_shape_with_options(
+    dataset: &Dataset,
+    muni_name: &str,
+    options: &ShapeOptions,
+) -> Result<MunicipalityShape> {
+    if PREFECTURES.iter().skip(1).any(|&pref| pref == muni_name) {
+        return prefecture_shape(dataset, muni_name, options);
+    }
+    if REGIONS.iter().any(|&(region, _)| region == muni_name) {
+        return region_shape(dataset, muni_name, options);
+    }
+
     // 都道府県名と、そのファイル内の欲しい境界データ番号を指定
     // 向日市
     // let (prefecture_name, feature_id) = ("京都府", 354);
@@ -61,18 +195,295 @@ pub fn municipality_shape(muni_name: &str, result_point_num: usize) -> ShapePoin
     // 姫路市
     // let (prefecture_name, feature_id) = ("兵庫県", 194);
 
-    // NOTE: 都道府県名一覧データがあるのでそちらを使っても良さそう
-    let re = Regex::new(r"([^\x00-\x7F]{2,3}県|..府|東京都|北海道)").unwrap();
-    let prefecture_name = re.captures(muni_name).unwrap().get(0).unwrap().as_str();
+    if is_muni_code(muni_name) {
+        let features = get_obj_has_specified_code(dataset, muni_name)?;
+        let Some(first) = features.first() else {
+            return Err(Error::MunicipalityNotFound {
+                name: muni_name.to_string(),
+                location: data_file_location(dataset, prefecture_name_from_code(muni_name)?)?,
+            });
+        };
+        let name = geo_feature_props_to_name(&first.properties);
+        return merge_features(&name, &features.iter().collect::<Vec<_>>(), options);
+    }
+
+    // 名前の先頭の都道府県名からファイルを決める。曖昧な名前は`name_search::search_names`で解決する
+    let prefecture_name = PREFECTURES
+        .iter()
+        .skip(1)
+        .find(|&&pref| muni_name.starts_with(pref))
+        .ok_or_else(|| Error::PrefectureNotInName(muni_name.to_string()))?;
+
+    let json_data = read_municipalities_boundary_data(dataset, prefecture_name)?;
+    // 指定された自治体を示すpropertiesを持つfeatureを全て集める
+    let mut features = vec![];
+    for feat in json_data.features.iter() {
+        if geo_feature_props_to_name(&feat.properties) == muni_name {
+            features.push(feat);
+        }
+    }
+    if features.is_empty() {
+        for feat in json_data.features.iter() {
+            for level in AreaLevel::ALL {
+                if geo_feature_props_to_area_name(&feat.properties, level).as_deref()
+                    == Some(muni_name)
+                {
+                    features.push(feat);
+                    break;
+                }
+            }
+        }
+        if !features.is_empty() {
+            return dissolve_features(muni_name, &features, options);
+        }
+        return Err(Error::MunicipalityNotFound {
+            name: muni_name.to_string(),
+            location: data_file_location(dataset, prefecture_name)?,
+        });
+    }
+    merge_features(muni_name, &features, options)
+}
+
+/// `dataset`から都道府県内の全featureを合併し、一つの境界形状として取得する。
+pub fn prefecture_shape(
+    dataset: &Dataset,
+    prefecture_name: &str,
+    options: &ShapeOptions,
+) -> Result<MunicipalityShape> {
+    let json_data = read_municipalities_boundary_data(dataset, prefecture_name)?;
+    dissolve_features(
+        prefecture_name,
+        &json_data.features.iter().collect::<Vec<_>>(),
+        options,
+    )
+}
+
+/// `dataset`から地方（近畿、九州など）に属する全都道府県の全featureを合併し、一つの境界形状として取得する。
+pub fn region_shape(
+    dataset: &Dataset,
+    region_name: &str,
+    options: &ShapeOptions,
+) -> Result<MunicipalityShape> {
+    let (_, prefectures) = REGIONS
+        .iter()
+        .find(|&&(region, _)| region == region_name)
+        .ok_or_else(|| Error::MunicipalityNotFound {
+            name: region_name.to_string(),
+            location: "regions".to_string(),
+        })?;
+    let mut features = vec![];
+    for prefecture_name in prefectures.iter() {
+        features.extend(read_municipalities_boundary_data(dataset, prefecture_name)?.features);
+    }
+    dissolve_features(region_name, &features.iter().collect::<Vec<_>>(), options)
+}
+
+/// エラーメッセージ用に、都道府県名とそのデータファイル名を並べた文字列を返す。
+fn data_file_location(dataset: &Dataset, prefecture_name: &str) -> Result<String> {
+    Ok(format!(
+        "{} ({})",
+        prefecture_name,
+        dataset.boundary_data_path(prefecture_name)?.display()
+    ))
+}
+
+/// 都道府県名を指定し、`dataset`からその都道府県内の全自治体の境界形状を取得する。
+/// 自治体はデータ内で最初に現れた順に並ぶ。
+/// `options.aggregate`が指定されていれば、その単位の区域に属する自治体は区域ごとに合併する。
+pub fn prefecture_municipality_shapes(
+    dataset: &Dataset,
+    prefecture_name: &str,
+    options: &ShapeOptions,
+) -> Result<Vec<MunicipalityShape>> {
+    let json_data = read_municipalities_boundary_data(dataset, prefecture_name)?;
+    let mut names = Vec::<(String, bool)>::new();
+    let mut features = HashMap::<String, Vec<&GeoFeature>>::new();
+    for feat in json_data.features.iter() {
+        let area_name = match options.aggregate {
+            Some(level) => geo_feature_props_to_area_name(&feat.properties, level),
+            None => None,
+        };
+        let is_area = area_name.is_some();
+        let name = match area_name {
+            Some(name) => name,
+            None => geo_feature_props_to_name(&feat.properties),
+        };
+        if !features.contains_key(&name) {
+            names.push((name.clone(), is_area));
+        }
+        features.entry(name).or_default().push(feat);
+    }
+    names
+        .into_iter()
+        .map(|(name, is_area)| {
+            if is_area {
+                dissolve_features(&name, &features[&name], options)
+            } else {
+                merge_features(&name, &features[&name], options)
+            }
+        })
+        .collect()
+}
+
+/// 任意のFeatureの列から、`mapping`のIDごと（IDが無ければ表示名ごと）に境界形状を取得する。
+/// 形状はデータ内で最初に現れた順に並び、IDを`code`とする。表示名が空の場合はIDあるいは通し番号を名前とする。
+pub fn mapped_feature_shapes(
+    features: &[GeoFeature],
+    mapping: &PropertyMapping,
+    options: &ShapeOptions,
+) -> Result<Vec<MunicipalityShape>> {
+    let mut keys = Vec::<(String, String, Option<String>)>::new();
+    let mut groups = HashMap::<String, Vec<&GeoFeature>>::new();
+    for (idx, feat) in features.iter().enumerate() {
+        let id = mapping.id(&feat.properties);
+        let name = match mapping.name(&feat.properties) {
+            name if !name.is_empty() => name,
+            _ => id.clone().unwrap_or_else(|| format!("#{}", idx)),
+        };
+        let key = id.clone().unwrap_or_else(|| name.clone());
+        if !groups.contains_key(&key) {
+            keys.push((key.clone(), name, id));
+        }
+        groups.entry(key).or_default().push(feat);
+    }
+    keys.into_iter()
+        .map(|(key, name, id)| {
+            let mut shape = merge_rings(&name, &groups[&key], options, false)?;
+            shape.code = id;
+            Ok(shape)
+        })
+        .collect()
+}
 
-    let json_data = read_municipalities_boundary_data(prefecture_name).unwrap();
-    // 指定された自治体を示すpropertiesを持つfeatureの中で要素数が最も多いものを探す
-    let geo_feature = json_data
-        .features
+/// 一つの自治体を構成するfeatureの列を、設定されたまとめ方に従って一つの境界形状にする。
+/// 穴と2つ目以降のポリゴンは`options.rings`に従って扱う。まとめる前に設定された投影を適用する。
+pub fn merge_features(
+    name: &str,
+    features: &[&GeoFeature],
+    options: &ShapeOptions,
+) -> Result<MunicipalityShape> {
+    let mut shape = merge_rings(name, features, options, false)?;
+    shape.code = features
         .iter()
-        .filter(|feat| geo_feature_props_to_name(&feat.properties) == muni_name)
-        .max_by_key(|x| x.geometry.coordinates.len())
-        .unwrap();
-    let shape = convert_to_shape(geo_feature, result_point_num);
-    normalize_shape(shape)
+        .find_map(|feat| geo_feature_props_to_code(&feat.properties));
+    Ok(shape)
+}
+
+/// 複数の自治体（政令指定都市の区など）のfeatureの列について、共有する境界を取り除いて合併した上で
+/// 設定されたまとめ方に従って一つの境界形状にする。区域全体の行政区域コードは持たない。
+pub fn dissolve_features(
+    name: &str,
+    features: &[&GeoFeature],
+    options: &ShapeOptions,
+) -> Result<MunicipalityShape> {
+    merge_rings(name, features, options, true)
+}
+
+/// featureの列から`options.rings`に従って外周と穴を取り出して投影し、
+/// `dissolve_first`なら外周を合併してから設定されたまとめ方を適用する。
+/// 含める穴は時計回りの環とし、一つの輪郭にまとめる場合は外周と幅0の橋でつなぎ、
+/// `MergeStrategy::MultiContour`では別々の輪郭とする。凸包では穴は形状に影響しない。
+/// まとめた輪郭は単純化してからリサンプリングする。
+fn merge_rings(
+    name: &str,
+    features: &[&GeoFeature],
+    options: &ShapeOptions,
+    dissolve_first: bool,
+) -> Result<MunicipalityShape> {
+    let to_points = |ring: &&LinearRing| {
+        ring.iter()
+            .map(|p_vec| Complex::new(p_vec[0], p_vec[1]))
+            .collect::<Vec<_>>()
+    };
+    let (mut rings, mut holes, mut reported) = (vec![], vec![], vec![]);
+    for feat in features.iter() {
+        let Some(feature_rings) = extract_rings(feat, options.rings) else {
+            continue;
+        };
+        rings.extend(feature_rings.exteriors.iter().map(to_points));
+        holes.extend(feature_rings.holes.iter().map(to_points));
+        reported.extend(feature_rings.reported);
+    }
+    if rings.is_empty() {
+        return Err(Error::NoPolygon {
+            name: name.to_string(),
+        });
+    }
+    // 外周と穴は同じ投影にする
+    let exterior_count = rings.len();
+    rings.extend(holes);
+    let mut rings = options.projection.project(&rings)?;
+    let holes = rings
+        .split_off(exterior_count)
+        .iter()
+        .map(|hole| {
+            let mut hole = counter_clockwise(hole);
+            hole.reverse();
+            hole
+        })
+        .collect::<Vec<_>>();
+    let exteriors = if dissolve_first {
+        dissolve(&rings)
+    } else {
+        rings
+    };
+
+    let contours = match options.strategy {
+        MergeStrategy::LargestArea => {
+            let largest = counter_clockwise(
+                exteriors
+                    .iter()
+                    .max_by(|a, b| signed_area(a).abs().total_cmp(&signed_area(b).abs()))
+                    .unwrap(),
+            );
+            let inner = holes
+                .into_iter()
+                .filter(|hole| contains_point(&largest, hole[0]))
+                .collect::<Vec<_>>();
+            if inner.is_empty() {
+                vec![largest]
+            } else {
+                vec![bridge_rings(&[vec![largest], inner].concat())]
+            }
+        }
+        MergeStrategy::Union => vec![bridge_rings(&[dissolve(&exteriors), holes].concat())],
+        MergeStrategy::ConvexHull => vec![convex_hull(&exteriors.concat())],
+        MergeStrategy::MultiContour => [dissolve(&exteriors), holes].concat(),
+    };
+    let mut simplification = SimplificationReport::default();
+    let contours = contours
+        .iter()
+        .map(|contour| {
+            let (simplified, report) = options
+                .simplification
+                .simplify(contour, options.measure_simplification);
+            simplification = simplification.combine(&report);
+            simplified
+        })
+        .collect::<Vec<_>>();
+    Ok(MunicipalityShape {
+        name: name.to_string(),
+        code: None,
+        strategy: options.strategy,
+        contours: resample_contours(&contours, options.sample_num, options.resampling)?,
+        simplification,
+        reported,
+    })
+}
+
+/// 複数の輪郭を周長に比例して点を配分しながら合計`sample_num`点程度にリサンプリングし、
+/// 全体として重心が原点となるよう正規化する。輪郭が一つの場合はちょうど`sample_num`点になる。
+/// 点の数が2未満の場合や、輪郭が一点に縮退している場合はエラーとする。
+fn resample_contours(
+    contours: &[Vec<Complex<f64>>],
+    sample_num: usize,
+    resampling: Resampling,
+) -> Result<Vec<ShapePoints>> {
+    let resampled = resample_rings(contours, sample_num, resampling)?;
+    let lengths = resampled.iter().map(|c| c.len()).collect::<Vec<_>>();
+    let mut normalized = normalize_shape(resampled.concat()).into_iter();
+    Ok(lengths
+        .into_iter()
+        .map(|len| normalized.by_ref().take(len).collect())
+        .collect())
 }
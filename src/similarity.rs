@@ -0,0 +1,212 @@
+//! フーリエ記述子を用いて二つの形状の類似度（距離）を計算する
+
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fft::FftNormalization,
+    municipalities::utils::normalize_shape,
+    shapes::ShapePoints,
+    spectrum::{Spectrum, TruncationPolicy, TruncationReport},
+};
+
+/// 記述子に用いる周波数の上限。正負それぞれこの数だけの係数を使う。
+pub const DESCRIPTOR_HARMONICS: usize = 16;
+/// 記述子の計算で用いている正規化の名前。保存された記述子との互換性の確認に使う。
+pub const DESCRIPTOR_NORMALIZATION: &str = "normalize_shape/non-dc-energy";
+
+/// 形状のフーリエ記述子。
+/// 周波数`1, -1, 2, -2, ...`の順に係数の大きさを並べたもの。
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FourierDescriptor(pub Vec<f64>);
+
+impl FourierDescriptor {
+    /// 点列からフーリエ記述子を計算する。
+    /// 直流成分を除くことで平行移動に、係数の大きさのみを使うことで回転と始点の取り方に、
+    /// 直流以外の全エネルギーで割ることで拡大縮小に対して不変になる。
+    pub fn new(shape: &[Complex<f64>], harmonics: usize) -> Self {
+        Self::with_policy(
+            shape,
+            harmonics,
+            TruncationPolicy::MaxFrequency(harmonics as u64),
+        )
+        .0
+    }
+
+    /// `policy`で係数を減らしてからフーリエ記述子を計算し、減らした結果の報告とともに返す。
+    /// 記述子の並びは`new`と同じく周波数`harmonics`までとし、減らした係数は0とする。
+    pub fn with_policy(
+        shape: &[Complex<f64>],
+        harmonics: usize,
+        policy: TruncationPolicy,
+    ) -> (Self, TruncationReport) {
+        let shape = normalize_shape(shape.to_vec());
+        // 記述子はエネルギーとの比なので正規化の種類によらないが、半径として読めるよう`1/N`で正規化する
+        let spectrum = Spectrum::new(&shape, FftNormalization::ByLength);
+        // 点数から正負ともに表現できる周波数までに制限する
+        let harmonics = harmonics.min(spectrum.len.saturating_sub(1) / 2);
+
+        // エネルギーは切り捨てる前の全ての係数で計算する
+        let energy = spectrum
+            .coefficients
+            .iter()
+            .filter(|c| c.freq != 0)
+            .map(|c| c.value.norm_sqr())
+            .sum::<f64>()
+            .sqrt();
+        let (spectrum, report) = spectrum.truncate(policy);
+        if energy == 0.0 {
+            return (Self(vec![0.0; 2 * harmonics]), report);
+        }
+        let values = (1..=harmonics as i64)
+            .flat_map(|k| [spectrum.coefficient(k), spectrum.coefficient(-k)])
+            .map(|c| c.norm() / energy)
+            .collect();
+        (Self(values), report)
+    }
+
+    /// 記述子同士のユークリッド距離を返す。
+    /// 長さが異なる場合、足りない係数は0として扱う。
+    pub fn distance(&self, other: &Self) -> f64 {
+        let (long, short) = if self.0.len() >= other.0.len() {
+            (&self.0, &other.0)
+        } else {
+            (&other.0, &self.0)
+        };
+        long.iter()
+            .enumerate()
+            .map(|(idx, a)| {
+                let b = short.get(idx).copied().unwrap_or(0.0);
+                (a - b).powi(2)
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+/// 二つの形状のフーリエ記述子の距離を返す。
+/// 平行移動・拡大縮小・回転・始点の取り方によらず、同じ形であれば0になる。
+#[allow(unused)]
+pub fn shape_distance(a: &ShapePoints, b: &ShapePoints) -> f64 {
+    FourierDescriptor::new(a, DESCRIPTOR_HARMONICS)
+        .distance(&FourierDescriptor::new(b, DESCRIPTOR_HARMONICS))
+}
+
+/// 名前付きの形状の組について、全ての組み合わせの距離を並べた行列。
+#[derive(Serialize, Debug)]
+pub struct DistanceMatrix {
+    /// 行・列のラベル
+    pub labels: Vec<String>,
+    /// `distances[i][j]`は`labels[i]`と`labels[j]`の距離
+    pub distances: Vec<Vec<f64>>,
+}
+
+impl DistanceMatrix {
+    /// 名前と形状の組の列から距離行列を作る。記述子は形状ごとに一度だけ計算する。
+    pub fn new(named_shapes: &[(String, ShapePoints)]) -> Self {
+        let descriptors = named_shapes
+            .iter()
+            .map(|(_, shape)| FourierDescriptor::new(shape, DESCRIPTOR_HARMONICS))
+            .collect::<Vec<_>>();
+        let distances = descriptors
+            .iter()
+            .map(|a| descriptors.iter().map(|b| a.distance(b)).collect())
+            .collect();
+        Self {
+            labels: named_shapes.iter().map(|(name, _)| name.clone()).collect(),
+            distances,
+        }
+    }
+}
+
+/// 回転・拡大縮小・平行移動・始点の変更を加えた`flower`が元の形と同一とみなされることを確かめる。
+#[test]
+fn test_flower_similarity_invariance() {
+    use crate::shapes::flower;
+
+    const EPS: f64 = 1e-10;
+    let original = flower();
+
+    let rotated = original
+        .iter()
+        .map(|c| c * Complex::cis(1.234))
+        .collect::<ShapePoints>();
+    assert!(shape_distance(&original, &rotated) < EPS);
+
+    let rescaled = original.iter().map(|c| c * 0.37).collect::<ShapePoints>();
+    assert!(shape_distance(&original, &rescaled) < EPS);
+
+    let translated = original
+        .iter()
+        .map(|c| c + Complex::new(-80.0, 45.0))
+        .collect::<ShapePoints>();
+    assert!(shape_distance(&original, &translated) < EPS);
+
+    let mut shifted = original.clone();
+    shifted.rotate_left(17);
+    assert!(shape_distance(&original, &shifted) < EPS);
+
+    let all = shifted
+        .iter()
+        .map(|c| c * Complex::from_polar(2.5, -0.8) + Complex::new(10.0, 3.0))
+        .collect::<ShapePoints>();
+    assert!(shape_distance(&original, &all) < EPS);
+}
+
+/// 異なる形同士の距離が十分大きくなることを確かめる。
+#[test]
+fn test_different_shapes_distance() {
+    use crate::shapes::{flower, rectangle, simple_circle};
+
+    let flower = flower();
+    assert!(shape_distance(&flower, &rectangle()) > 0.1);
+    assert!(shape_distance(&flower, &simple_circle()) > 0.1);
+    assert!(shape_distance(&rectangle(), &simple_circle()) > 0.01);
+}
+
+/// 距離行列が対称で対角成分が0になることを確かめる。
+#[test]
+fn test_distance_matrix() {
+    use crate::shapes::{flower, rectangle, simple_circle};
+
+    let named_shapes = vec![
+        ("flower".to_string(), flower()),
+        ("rectangle".to_string(), rectangle()),
+        ("circle".to_string(), simple_circle()),
+    ];
+    let matrix = DistanceMatrix::new(&named_shapes);
+    assert_eq!(matrix.labels, vec!["flower", "rectangle", "circle"]);
+    for i in 0..3 {
+        assert_eq!(matrix.distances[i][i], 0.0);
+        for j in 0..3 {
+            assert_eq!(matrix.distances[i][j], matrix.distances[j][i]);
+        }
+    }
+    assert_eq!(
+        matrix.distances[0][1],
+        shape_distance(&named_shapes[0].1, &named_shapes[1].1)
+    );
+}
+
+/// 係数を減らす方法を指定した記述子が、残した係数のみを用い、周波数での制限が`new`と一致することを確かめる。
+#[test]
+fn test_descriptor_truncation() {
+    use crate::shapes::flower;
+
+    let flower = flower();
+    let full = FourierDescriptor::new(&flower, DESCRIPTOR_HARMONICS);
+    let (limited, report) = FourierDescriptor::with_policy(
+        &flower,
+        DESCRIPTOR_HARMONICS,
+        TruncationPolicy::MaxFrequency(DESCRIPTOR_HARMONICS as u64),
+    );
+    assert_eq!(full, limited);
+    assert_eq!(report.kept, 2 * DESCRIPTOR_HARMONICS + 1);
+
+    let (top, report) =
+        FourierDescriptor::with_policy(&flower, DESCRIPTOR_HARMONICS, TruncationPolicy::TopK(3));
+    assert_eq!(report.kept, 3);
+    assert_eq!(top.0.len(), full.0.len());
+    assert!(top.0.iter().filter(|v| **v > 0.0).count() <= 3);
+    assert!(top.distance(&full) < 0.1);
+}
@@ -0,0 +1,274 @@
+//! リサンプリングの前に境界の頂点を間引いて単純化する
+
+use std::{cmp::Ordering, collections::BinaryHeap, str::FromStr};
+
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    geometry::{close_ring, hausdorff_distance, open_ring, segment_distance},
+};
+
+/// 環の単純化の方法。許容誤差は投影後の座標の単位で与える。
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub enum Simplification {
+    /// 単純化しない
+    #[default]
+    None,
+    /// Douglas–Peucker法。取り除いた頂点から代わりの辺までの距離が許容誤差以下になるようにする
+    DouglasPeucker(f64),
+    /// Visvalingam–Whyatt法。前後の頂点とがなす三角形の面積が許容値未満の頂点から順に取り除く
+    VisvalingamWhyatt(f64),
+}
+
+/// `none`、`dp:<許容誤差>`、`vw:<面積>`の形の文字列から単純化の方法を作る。
+impl FromStr for Simplification {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidOption {
+            kind: "simplification",
+            value: s.to_string(),
+        };
+        if s == "none" {
+            return Ok(Self::None);
+        }
+        let (method, tolerance) = s.split_once(':').ok_or_else(invalid)?;
+        let tolerance = tolerance
+            .parse::<f64>()
+            .ok()
+            .filter(|t| *t >= 0.0)
+            .ok_or_else(invalid)?;
+        match method {
+            "dp" => Ok(Self::DouglasPeucker(tolerance)),
+            "vw" => Ok(Self::VisvalingamWhyatt(tolerance)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// 単純化の結果
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+pub struct SimplificationReport {
+    /// 元の頂点の数（閉じるための最後の点は数えない）
+    pub original: usize,
+    /// 取り除いた頂点の数
+    pub removed: usize,
+    /// 単純化前後の境界のハウスドルフ距離（`geometry::hausdorff_distance`）。測らなかった場合は`None`
+    pub hausdorff: Option<f64>,
+}
+
+impl SimplificationReport {
+    /// 複数の環の結果をまとめる。
+    pub fn combine(&self, other: &Self) -> Self {
+        Self {
+            original: self.original + other.original,
+            removed: self.removed + other.removed,
+            hausdorff: match (self.hausdorff, other.hausdorff) {
+                (Some(a), Some(b)) => Some(a.max(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+impl Simplification {
+    /// 環を単純化し、閉じた環と結果を返す。少なくとも3頂点は残す。
+    /// `measure_error`の場合は単純化前後のハウスドルフ距離も測る（頂点数の積に比例する時間がかかる）。
+    pub fn simplify(
+        &self,
+        ring: &[Complex<f64>],
+        measure_error: bool,
+    ) -> (Vec<Complex<f64>>, SimplificationReport) {
+        let points = open_ring(ring);
+        let keep = match *self {
+            _ if points.len() <= 3 => vec![true; points.len()],
+            Self::None => vec![true; points.len()],
+            Self::DouglasPeucker(tolerance) => douglas_peucker(points, tolerance),
+            Self::VisvalingamWhyatt(min_area) => visvalingam_whyatt(points, min_area),
+        };
+        let simplified = points
+            .iter()
+            .zip(&keep)
+            .filter(|(_, &k)| k)
+            .map(|(&p, _)| p)
+            .collect::<Vec<_>>();
+        let removed = points.len() - simplified.len();
+        let report = SimplificationReport {
+            original: points.len(),
+            removed,
+            hausdorff: match (measure_error, removed) {
+                (false, _) => None,
+                (true, 0) => Some(0.0),
+                (true, _) => Some(hausdorff_distance(points, &simplified)),
+            },
+        };
+        (close_ring(simplified), report)
+    }
+}
+
+/// 開いた環にDouglas–Peucker法を適用し、残す頂点を返す。
+/// 最初の頂点と、そこから最も遠い頂点で環を二つに分けてそれぞれを単純化する。
+fn douglas_peucker(points: &[Complex<f64>], tolerance: f64) -> Vec<bool> {
+    let n = points.len();
+    let farthest = (1..n)
+        .max_by(|&a, &b| {
+            (points[a] - points[0])
+                .norm()
+                .total_cmp(&(points[b] - points[0]).norm())
+        })
+        .unwrap();
+    let mut keep = vec![false; n];
+    keep[0] = true;
+    keep[farthest] = true;
+    // 区間(始点, 終点)を積んで処理する。終点の添字nは最初の頂点を表す
+    let mut stack = vec![(0, farthest), (farthest, n)];
+    while let Some((start, end)) = stack.pop() {
+        let (a, b) = (points[start], points[end % n]);
+        let Some((idx, distance)) = (start + 1..end)
+            .map(|i| (i, segment_distance(points[i], a, b)))
+            .max_by(|(_, x), (_, y)| x.total_cmp(y))
+        else {
+            continue;
+        };
+        if distance > tolerance {
+            keep[idx] = true;
+            stack.push((start, idx));
+            stack.push((idx, end));
+        }
+    }
+    keep
+}
+
+/// 三角形の面積をキーとするヒープの要素。面積の小さい順に取り出す。
+#[derive(PartialEq)]
+struct AreaEntry {
+    area: f64,
+    idx: usize,
+    /// 取り出した時点で頂点の面積が更新されていないかの確認に使う
+    version: usize,
+}
+
+impl Eq for AreaEntry {}
+
+impl Ord for AreaEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .area
+            .total_cmp(&self.area)
+            .then(other.idx.cmp(&self.idx))
+    }
+}
+
+impl PartialOrd for AreaEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// 開いた環にVisvalingam–Whyatt法を適用し、残す頂点を返す。
+fn visvalingam_whyatt(points: &[Complex<f64>], min_area: f64) -> Vec<bool> {
+    let n = points.len();
+    let mut prev = (0..n).map(|i| (i + n - 1) % n).collect::<Vec<_>>();
+    let mut next = (0..n).map(|i| (i + 1) % n).collect::<Vec<_>>();
+    let mut versions = vec![0usize; n];
+    let area = |prev: usize, idx: usize, next: usize| {
+        let (a, b) = (points[idx] - points[prev], points[next] - points[prev]);
+        (a.re * b.im - a.im * b.re).abs() / 2.0
+    };
+    let mut heap = (0..n)
+        .map(|idx| AreaEntry {
+            area: area(prev[idx], idx, next[idx]),
+            idx,
+            version: 0,
+        })
+        .collect::<BinaryHeap<_>>();
+    let mut keep = vec![true; n];
+    let mut remaining = n;
+    // 取り除いた頂点の面積より小さくならないようにする（Visvalingamの補正）
+    let mut max_removed_area = 0.0f64;
+    while let Some(entry) = heap.pop() {
+        if remaining <= 3 || entry.area >= min_area {
+            break;
+        }
+        if !keep[entry.idx] || entry.version != versions[entry.idx] {
+            continue;
+        }
+        max_removed_area = max_removed_area.max(entry.area);
+        let (p, q) = (prev[entry.idx], next[entry.idx]);
+        keep[entry.idx] = false;
+        remaining -= 1;
+        next[p] = q;
+        prev[q] = p;
+        for idx in [p, q] {
+            versions[idx] += 1;
+            heap.push(AreaEntry {
+                area: area(prev[idx], idx, next[idx]).max(max_removed_area),
+                idx,
+                version: versions[idx],
+            });
+        }
+    }
+    keep
+}
+
+/// いずれの方法でも、許容誤差以下の揺らぎを取り除きつつ大きな形を保つことを確かめる。
+#[test]
+fn test_simplification() {
+    use std::f64::consts::TAU;
+
+    // 1x1の正方形の各辺に細かい頂点と振幅1e-3の揺らぎを加える
+    let corners = [
+        Complex::new(0.0, 0.0),
+        Complex::new(1.0, 0.0),
+        Complex::new(1.0, 1.0),
+        Complex::new(0.0, 1.0),
+    ];
+    let mut ring = vec![];
+    for (idx, &a) in corners.iter().enumerate() {
+        let b = corners[(idx + 1) % 4];
+        let normal = (b - a) * Complex::new(0.0, -1.0);
+        for i in 0..50 {
+            let t = i as f64 / 50.0;
+            ring.push(a + (b - a) * t + normal * 1e-3 * (TAU * t * 7.0).sin());
+        }
+    }
+    let ring = close_ring(ring);
+
+    for simplification in [
+        Simplification::DouglasPeucker(1e-2),
+        Simplification::VisvalingamWhyatt(1e-3),
+    ] {
+        let (simplified, report) = simplification.simplify(&ring, true);
+        assert_eq!(report.original, 200);
+        assert_eq!(simplified.len(), 4 + 1, "{:?}", simplification);
+        assert_eq!(report.removed, 196);
+        let hausdorff = report.hausdorff.unwrap();
+        assert!(hausdorff > 5e-4 && hausdorff <= 1e-3 + 1e-12);
+        assert_eq!(hausdorff, hausdorff_distance(&ring, &simplified));
+        // 測らない場合も結果の環は同じ
+        let (unmeasured, report) = simplification.simplify(&ring, false);
+        assert_eq!(unmeasured, simplified);
+        assert_eq!(report.hausdorff, None);
+        for corner in corners {
+            assert!(simplified.contains(&corner));
+        }
+    }
+
+    // 許容誤差が揺らぎより小さければ頂点はほとんど残る
+    let (_, report) = Simplification::DouglasPeucker(1e-5).simplify(&ring, true);
+    assert!(report.removed < 20);
+    assert!(report.hausdorff.unwrap() <= 1e-5);
+    let (unchanged, report) = Simplification::None.simplify(&ring, true);
+    assert_eq!(unchanged, ring);
+    assert_eq!(report.removed, 0);
+    assert_eq!(report.hausdorff, Some(0.0));
+
+    assert_eq!(
+        "dp:0.5".parse::<Simplification>().unwrap(),
+        Simplification::DouglasPeucker(0.5)
+    );
+    assert!("dp".parse::<Simplification>().is_err());
+    assert!("xx:1".parse::<Simplification>().is_err());
+}
@@ -0,0 +1,658 @@
+//! FFTの結果の各係数に符号付きの周波数を付けて扱う
+
+use std::{f64::consts::TAU, str::FromStr};
+
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::{Error, Result},
+    fft::{fft_points, ifft_points, FftNormalization},
+    geometry::hausdorff_distance,
+    shapes::ShapePoints,
+};
+
+/// 符号付きの周波数を持つフーリエ係数
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Coefficient {
+    /// 一周あたりの回転数。負なら時計回りに回る
+    pub freq: i64,
+    /// FFTの結果を`Spectrum::normalization`で正規化したもの
+    pub value: Complex<f64>,
+}
+
+/// 点列のフーリエ係数の集まり。
+/// 長さ`N`のFFTの添字`k`は、`k < N/2`なら周波数`k`、それ以外は周波数`k - N`として扱う。
+/// すなわち周波数は`-N/2..N/2`の範囲になる。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spectrum {
+    /// 元の点列の長さ`N`
+    pub len: usize,
+    /// 係数に適用されている正規化
+    pub normalization: FftNormalization,
+    /// 係数の列。切り捨てた係数は含まない
+    pub coefficients: Vec<Coefficient>,
+}
+
+/// 長さ`len`のFFTの添字`bin`に対応する符号付きの周波数を返す。
+pub fn signed_frequency(bin: usize, len: usize) -> i64 {
+    if 2 * bin < len {
+        bin as i64
+    } else {
+        bin as i64 - len as i64
+    }
+}
+
+impl Spectrum {
+    /// 点列（始点から一周する閉じた曲線を等間隔に標本化したもの）にFFTを適用し、`normalization`で正規化する。
+    pub fn new(points: &[Complex<f64>], normalization: FftNormalization) -> Self {
+        Self::from_bins(fft_points(points, normalization), normalization)
+    }
+
+    /// `normalization`で正規化されたFFTの結果を添字の順に与える。
+    pub fn from_bins(bins: Vec<Complex<f64>>, normalization: FftNormalization) -> Self {
+        let len = bins.len();
+        let coefficients = bins
+            .into_iter()
+            .enumerate()
+            .map(|(bin, value)| Coefficient {
+                freq: signed_frequency(bin, len),
+                value,
+            })
+            .collect();
+        Self {
+            len,
+            normalization,
+            coefficients,
+        }
+    }
+
+    /// 閉じた多角形を弧長で一周1にパラメータ付けした曲線とみなし、そのフーリエ係数を辺ごとに解析的に求める。
+    /// 長さ`len`のFFTと同じ周波数の係数を`normalization`で正規化した値で返すので、
+    /// 弧長で等間隔に`len`点を取ってFFTした結果とは折り返しの誤差を除いて一致する。
+    /// 始点は最初の頂点とし、閉じていない場合は最後の頂点から最初の頂点への辺を補う。
+    pub fn from_polygon(
+        vertices: &[Complex<f64>],
+        len: usize,
+        normalization: FftNormalization,
+    ) -> Self {
+        let mut ring = vertices.to_vec();
+        if let (Some(&first), Some(&last)) = (ring.first(), ring.last()) {
+            if first != last {
+                ring.push(first);
+            }
+        }
+        let total_length = ring
+            .windows(2)
+            .map(|pair| (pair[1] - pair[0]).norm())
+            .sum::<f64>();
+        // 長さのある辺ごとに、始点と終点のパラメータと、パラメータあたりの変化（速度）
+        let mut segments = vec![];
+        let mut t = 0.0;
+        for pair in ring.windows(2) {
+            let length = (pair[1] - pair[0]).norm();
+            if length == 0.0 {
+                continue;
+            }
+            let dt = length / total_length;
+            segments.push((pair[0], pair[1], t, t + dt, (pair[1] - pair[0]) / dt));
+            t += dt;
+        }
+
+        let radius_factor = normalization.radius_factor(len);
+        let coefficients = (0..len)
+            .map(|bin| {
+                let freq = signed_frequency(bin, len);
+                let value = if segments.is_empty() {
+                    // 長さが0の場合は始点に留まる
+                    if freq == 0 {
+                        ring.first().copied().unwrap_or_default()
+                    } else {
+                        Complex::new(0.0, 0.0)
+                    }
+                } else if freq == 0 {
+                    // 各辺の中点を辺の長さで重み付けした平均
+                    segments
+                        .iter()
+                        .map(|&(a, b, t0, t1, _)| (a + b) / 2.0 * (t1 - t0))
+                        .sum()
+                } else {
+                    // 閉じた曲線なので部分積分の境界項は打ち消し合い、各辺の速度（定数）の積分だけが残る
+                    let omega = TAU * freq as f64;
+                    -segments
+                        .iter()
+                        .map(|&(_, _, t0, t1, velocity)| {
+                            velocity * (Complex::cis(-omega * t0) - Complex::cis(-omega * t1))
+                        })
+                        .sum::<Complex<f64>>()
+                        / (omega * omega)
+                };
+                Coefficient {
+                    freq,
+                    value: value / radius_factor,
+                }
+            })
+            .collect();
+        Self {
+            len,
+            normalization,
+            coefficients,
+        }
+    }
+
+    /// 係数の値を円の半径と中心のずれ（`1/N`で正規化した値）に換算する。
+    pub fn amplitude(&self, value: Complex<f64>) -> Complex<f64> {
+        value * self.normalization.radius_factor(self.len)
+    }
+
+    /// 周波数`freq`の係数を返す。切り捨てられているか範囲外なら0を返す。
+    pub fn coefficient(&self, freq: i64) -> Complex<f64> {
+        self.coefficients
+            .iter()
+            .find(|c| c.freq == freq)
+            .map_or(Complex::new(0.0, 0.0), |c| c.value)
+    }
+
+    /// 大きさの大きい順に`k`個の係数を残す。結果の係数は大きさの降順に並ぶ。
+    pub fn largest(&self, k: usize) -> Self {
+        let mut coefficients = self.coefficients.clone();
+        coefficients.sort_by(|a, b| b.value.norm().total_cmp(&a.value.norm()));
+        coefficients.truncate(k);
+        Self {
+            len: self.len,
+            normalization: self.normalization,
+            coefficients,
+        }
+    }
+
+    /// 周波数の絶対値が`max_freq`以下の係数を残す。
+    pub fn low_pass(&self, max_freq: u64) -> Self {
+        Self {
+            len: self.len,
+            normalization: self.normalization,
+            coefficients: self
+                .coefficients
+                .iter()
+                .filter(|c| c.freq.unsigned_abs() <= max_freq)
+                .copied()
+                .collect(),
+        }
+    }
+
+    /// 周波数の絶対値の小さい順に`k`個の係数を残す。絶対値が等しい場合は正の周波数を先にする。
+    pub fn lowest(&self, k: usize) -> Self {
+        let mut coefficients = self.coefficients.clone();
+        coefficients.sort_by_key(|c| (c.freq.unsigned_abs(), c.freq < 0));
+        coefficients.truncate(k);
+        Self {
+            len: self.len,
+            normalization: self.normalization,
+            coefficients,
+        }
+    }
+
+    /// `selection`に従って`k`個の係数を残す。
+    pub fn select(&self, selection: HarmonicSelection, k: usize) -> Self {
+        match selection {
+            HarmonicSelection::MostSignificant => self.largest(k),
+            HarmonicSelection::LowestFrequency => self.lowest(k),
+        }
+    }
+
+    /// `policy`に従って係数を減らし、残したものの報告とともに返す。結果の係数は大きさの降順に並ぶ。
+    pub fn truncate(&self, policy: TruncationPolicy) -> (Self, TruncationReport) {
+        let sorted = self.largest(self.coefficients.len());
+        let coefficients = match policy {
+            TruncationPolicy::TopK(k) => sorted.coefficients.into_iter().take(k).collect(),
+            TruncationPolicy::MaxFrequency(max_freq) => {
+                let low = self.low_pass(max_freq);
+                low.largest(low.coefficients.len()).coefficients
+            }
+            TruncationPolicy::Energy(percent) => {
+                let target = self.energy() * percent / 100.0;
+                let mut kept_energy = 0.0;
+                sorted
+                    .coefficients
+                    .into_iter()
+                    .take_while(|c| {
+                        let keep = kept_energy < target;
+                        kept_energy += c.value.norm_sqr();
+                        keep
+                    })
+                    .collect()
+            }
+            TruncationPolicy::MagnitudeThreshold(threshold) => sorted
+                .coefficients
+                .into_iter()
+                .filter(|c| self.amplitude(c.value).norm() >= threshold)
+                .collect(),
+        };
+        let truncated = Self {
+            len: self.len,
+            normalization: self.normalization,
+            coefficients,
+        };
+        let total_energy = self.energy();
+        let report = TruncationReport {
+            policy,
+            total: self.coefficients.len(),
+            kept: truncated.coefficients.len(),
+            energy_ratio: if total_energy > 0.0 {
+                truncated.energy() / total_energy
+            } else {
+                1.0
+            },
+            max_frequency: truncated
+                .coefficients
+                .iter()
+                .map(|c| c.freq.unsigned_abs())
+                .max()
+                .unwrap_or(0),
+        };
+        (truncated, report)
+    }
+
+    /// 残っている係数の大きさの二乗和。値は正規化の種類によって異なる
+    pub fn energy(&self) -> f64 {
+        self.coefficients.iter().map(|c| c.value.norm_sqr()).sum()
+    }
+
+    /// 残っている係数から、一周を等間隔に`resolution`点に分けた位置の点列を求める（始点は重複させない）。
+    /// 全ての係数の周波数が`resolution`点で表現できる範囲にあれば逆FFTを使い、
+    /// 範囲外の係数は`evaluate`で直接足し合わせる。
+    pub fn reconstruct(&self, resolution: usize) -> ShapePoints {
+        if resolution == 0 {
+            return vec![];
+        }
+        let mut bins = vec![Complex::new(0.0, 0.0); resolution];
+        let mut direct = Self {
+            len: self.len,
+            normalization: self.normalization,
+            coefficients: vec![],
+        };
+        for c in self.coefficients.iter() {
+            let bin = c.freq.rem_euclid(resolution as i64) as usize;
+            if signed_frequency(bin, resolution) == c.freq {
+                bins[bin] += c.value;
+            } else {
+                direct.coefficients.push(*c);
+            }
+        }
+        ifft_points(&bins)
+            .into_iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                let t = idx as f64 / resolution as f64;
+                self.amplitude(p) + direct.evaluate(t)
+            })
+            .collect()
+    }
+
+    /// 残っている係数から、曲線上の位置`t`（0で始点、1で一周）の点を求める。
+    /// `t`が標本点の間にあっても、符号付きの周波数で回すので滑らかに補間される。
+    pub fn evaluate(&self, t: f64) -> Complex<f64> {
+        self.amplitude(
+            self.coefficients
+                .iter()
+                .map(|c| c.value * Complex::cis(TAU * c.freq as f64 * t))
+                .sum::<Complex<f64>>(),
+        )
+    }
+}
+
+/// 係数を残す際の選び方
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HarmonicSelection {
+    /// 大きさの大きい順
+    #[default]
+    MostSignificant,
+    /// 周波数の絶対値の小さい順
+    LowestFrequency,
+}
+
+impl FromStr for HarmonicSelection {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "largest" => Ok(Self::MostSignificant),
+            "lowest" => Ok(Self::LowestFrequency),
+            other => Err(Error::InvalidOption {
+                kind: "harmonic selection",
+                value: other.to_string(),
+            }),
+        }
+    }
+}
+
+/// 係数の減らし方
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum TruncationPolicy {
+    /// 大きさの大きい順に指定された個数を残す
+    TopK(usize),
+    /// 周波数の絶対値が指定された値以下のものを残す
+    MaxFrequency(u64),
+    /// 大きさの大きい順に、全エネルギー（大きさの二乗和）の指定された割合（%）に達するまで残す
+    Energy(f64),
+    /// 円の半径（大きさを点の数で割ったもの）が指定された値以上のものを残す
+    MagnitudeThreshold(f64),
+}
+
+/// `top:<個数>`、`freq:<周波数>`、`energy:<%>`、`threshold:<半径>`の形の文字列から減らし方を作る。
+impl FromStr for TruncationPolicy {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidOption {
+            kind: "truncation policy",
+            value: s.to_string(),
+        };
+        let (method, value) = s.split_once(':').ok_or_else(invalid)?;
+        match method {
+            "top" => value.parse().map(Self::TopK).map_err(|_| invalid()),
+            "freq" => value.parse().map(Self::MaxFrequency).map_err(|_| invalid()),
+            "energy" => value
+                .parse::<f64>()
+                .ok()
+                .filter(|p| (0.0..=100.0).contains(p))
+                .map(Self::Energy)
+                .ok_or_else(invalid),
+            "threshold" => value
+                .parse::<f64>()
+                .ok()
+                .filter(|t| *t >= 0.0)
+                .map(Self::MagnitudeThreshold)
+                .ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// 係数を減らした結果
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct TruncationReport {
+    pub policy: TruncationPolicy,
+    /// 元の係数の個数
+    pub total: usize,
+    /// 残した係数の個数
+    pub kept: usize,
+    /// 残した係数のエネルギーの、元の全エネルギーに対する割合
+    pub energy_ratio: f64,
+    /// 残した係数の周波数の絶対値の最大値
+    pub max_frequency: u64,
+}
+
+/// 係数を`harmonics`個に減らして再構成した際の誤差
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct ReconstructionError {
+    pub harmonics: usize,
+    /// 元の各点と、同じ位置で再構成した点との距離の二乗平均平方根
+    pub rms: f64,
+    /// `resolution`点で再構成した曲線と元の曲線とのハウスドルフ距離
+    pub hausdorff: f64,
+}
+
+/// 点列の係数を`selection`に従って1個から`max_harmonics`個まで増やしながら再構成し、それぞれの誤差を返す。
+/// ハウスドルフ距離は`resolution`点で再構成した曲線について求める。
+pub fn reconstruction_errors(
+    shape: &[Complex<f64>],
+    selection: HarmonicSelection,
+    max_harmonics: usize,
+    resolution: usize,
+) -> Vec<ReconstructionError> {
+    let spectrum = Spectrum::new(shape, FftNormalization::None);
+    (1..=max_harmonics.min(spectrum.len))
+        .map(|harmonics| {
+            let truncated = spectrum.select(selection, harmonics);
+            let at_samples = truncated.reconstruct(spectrum.len);
+            let rms = (shape
+                .iter()
+                .zip(&at_samples)
+                .map(|(a, b)| (a - b).norm_sqr())
+                .sum::<f64>()
+                / shape.len() as f64)
+                .sqrt();
+            ReconstructionError {
+                harmonics,
+                rms,
+                hausdorff: hausdorff_distance(&truncated.reconstruct(resolution), shape),
+            }
+        })
+        .collect()
+}
+
+/// 負の周波数を含む曲線について、標本点の間でも元の曲線を再現できることを確かめる。
+#[test]
+fn test_spectrum_smooth_reconstruction() {
+    const N: usize = 64;
+    // 反時計回りの円に、時計回りに4回まわる円を重ねた曲線
+    let curve = |t: f64| 150.0 * Complex::cis(TAU * t) + 50.0 * Complex::cis(-TAU * 4.0 * t);
+    let points = (0..N)
+        .map(|idx| curve(idx as f64 / N as f64))
+        .collect::<Vec<_>>();
+    let spectrum = Spectrum::new(&points, FftNormalization::None);
+
+    assert_eq!(spectrum.len, N);
+    assert!((spectrum.coefficient(1).norm() - 150.0 * N as f64).abs() < 1e-9);
+    assert!((spectrum.coefficient(-4).norm() - 50.0 * N as f64).abs() < 1e-9);
+    assert!(spectrum.coefficient(N as i64 - 4).norm() == 0.0);
+    let freqs = spectrum
+        .largest(2)
+        .coefficients
+        .iter()
+        .map(|c| c.freq)
+        .collect::<Vec<_>>();
+    assert_eq!(freqs, [1, -4]);
+
+    // 標本点の間を細かくたどっても元の曲線に一致する
+    for idx in 0..N * 8 {
+        let t = (idx as f64 + 0.37) / (N * 8) as f64;
+        assert!((spectrum.evaluate(t) - curve(t)).norm() < 1e-9);
+        assert!((spectrum.largest(2).evaluate(t) - curve(t)).norm() < 1e-9);
+    }
+    // 添字をそのまま周波数とみなすと、標本点では一致しても間では大きくずれる
+    let unsigned = Spectrum {
+        len: N,
+        normalization: FftNormalization::None,
+        coefficients: spectrum
+            .coefficients
+            .iter()
+            .map(|c| Coefficient {
+                freq: c.freq.rem_euclid(N as i64),
+                value: c.value,
+            })
+            .collect(),
+    };
+    assert!((unsigned.evaluate(3.0 / N as f64) - curve(3.0 / N as f64)).norm() < 1e-9);
+    assert!((unsigned.evaluate(3.5 / N as f64) - curve(3.5 / N as f64)).norm() > 1.0);
+
+    // 低域通過では時計回りの成分が周波数の絶対値で切られる
+    assert_eq!(spectrum.low_pass(3).coefficient(-4), Complex::new(0.0, 0.0));
+    assert_eq!(spectrum.low_pass(4).coefficients.len(), 9);
+    assert_eq!(signed_frequency(N / 2, N), -(N as i64) / 2);
+    assert_eq!(signed_frequency(2, 5), 2);
+    assert_eq!(signed_frequency(3, 5), -2);
+}
+
+/// 任意の点数で再構成でき、係数を増やすほど誤差が小さくなり全ての係数で元に戻ることを確かめる。
+#[test]
+fn test_reconstruction() {
+    use crate::shapes::rectangle;
+
+    const N: usize = 32;
+    let curve = |t: f64| 150.0 * Complex::cis(TAU * t) + 50.0 * Complex::cis(-TAU * 4.0 * t);
+    let points = (0..N)
+        .map(|idx| curve(idx as f64 / N as f64))
+        .collect::<Vec<_>>();
+    let spectrum = Spectrum::new(&points, FftNormalization::None);
+
+    // 逆FFTで元の点数に戻すと元の点列になる
+    for (a, b) in spectrum.reconstruct(N).iter().zip(&points) {
+        assert!((a - b).norm() < 1e-9);
+    }
+    // 点数を増やしても減らしても曲線上の点になる。周波数-4は8点未満では直接足し合わせる
+    for resolution in [5, 8, 100, 257] {
+        let reconstructed = spectrum.largest(2).reconstruct(resolution);
+        assert_eq!(reconstructed.len(), resolution);
+        for (idx, p) in reconstructed.iter().enumerate() {
+            assert!((p - curve(idx as f64 / resolution as f64)).norm() < 1e-9);
+        }
+    }
+    assert!(spectrum.reconstruct(0).is_empty());
+
+    // 周波数の小さい順に選ぶと0, 1, -1, 2, ...の順になる
+    let freqs = spectrum
+        .lowest(4)
+        .coefficients
+        .iter()
+        .map(|c| c.freq)
+        .collect::<Vec<_>>();
+    assert_eq!(freqs, [0, 1, -1, 2]);
+
+    let rectangle = rectangle();
+    for selection in [
+        HarmonicSelection::MostSignificant,
+        HarmonicSelection::LowestFrequency,
+    ] {
+        let errors = reconstruction_errors(&rectangle, selection, rectangle.len(), 128);
+        assert_eq!(errors.len(), rectangle.len());
+        assert!(errors[2].rms > errors[20].rms);
+        assert!(errors[2].hausdorff > errors[20].hausdorff);
+        let last = errors.last().unwrap();
+        assert!(last.rms < 1e-9, "{:?}", selection);
+    }
+    let errors = reconstruction_errors(&points, HarmonicSelection::MostSignificant, 3, 256);
+    assert!(errors[0].rms > 1.0);
+    assert!(errors[1].rms < 1e-9);
+    // ハウスドルフ距離は元の点列を結んだ折れ線と比べるので、その分の差は残る
+    assert!(errors[1].hausdorff < errors[0].hausdorff);
+    assert_eq!(
+        "lowest".parse::<HarmonicSelection>().unwrap(),
+        HarmonicSelection::LowestFrequency
+    );
+}
+
+/// 各減らし方で残す係数と、その報告を確かめる。
+#[test]
+fn test_truncation_policy() {
+    const N: usize = 64;
+    // 半径100、20、5、1の円を周波数1、-2、3、-7で重ねた曲線
+    let terms = [(100.0, 1), (20.0, -2), (5.0, 3), (1.0, -7)];
+    let points = (0..N)
+        .map(|idx| {
+            let t = idx as f64 / N as f64;
+            terms
+                .iter()
+                .map(|&(r, f)| r * Complex::cis(TAU * f as f64 * t))
+                .sum::<Complex<f64>>()
+        })
+        .collect::<Vec<_>>();
+    let spectrum = Spectrum::new(&points, FftNormalization::None);
+    let kept_freqs = |policy: TruncationPolicy| {
+        let (truncated, report) = spectrum.truncate(policy);
+        assert_eq!(report.kept, truncated.coefficients.len());
+        assert_eq!(report.total, N);
+        let mut freqs = truncated
+            .coefficients
+            .iter()
+            .filter(|c| c.value.norm() > 1e-6)
+            .map(|c| c.freq)
+            .collect::<Vec<_>>();
+        freqs.sort();
+        (freqs, report)
+    };
+
+    let (freqs, report) = kept_freqs(TruncationPolicy::TopK(2));
+    assert_eq!(freqs, [-2, 1]);
+    assert_eq!(report.max_frequency, 2);
+    let total = 100f64.powi(2) + 20f64.powi(2) + 5f64.powi(2) + 1.0;
+    assert!((report.energy_ratio - (100f64.powi(2) + 20f64.powi(2)) / total).abs() < 1e-9);
+
+    let (freqs, report) = kept_freqs(TruncationPolicy::MaxFrequency(3));
+    assert_eq!(freqs, [-2, 1, 3]);
+    assert_eq!(report.kept, 7);
+
+    // 99%は半径100と20の円で足りるが、99.9%には半径5の円まで必要
+    let (freqs, _) = kept_freqs(TruncationPolicy::Energy(99.0));
+    assert_eq!(freqs, [-2, 1]);
+    let (freqs, report) = kept_freqs(TruncationPolicy::Energy(99.9));
+    assert_eq!(freqs, [-2, 1, 3]);
+    assert_eq!(report.kept, 3);
+    assert!(report.energy_ratio >= 0.999);
+    let (freqs, _) = kept_freqs(TruncationPolicy::Energy(100.0));
+    assert_eq!(freqs, [-7, -2, 1, 3]);
+
+    let (freqs, report) = kept_freqs(TruncationPolicy::MagnitudeThreshold(4.0));
+    assert_eq!(freqs, [-2, 1, 3]);
+    assert_eq!(report.max_frequency, 3);
+
+    // 大きさの降順に並ぶ
+    let (truncated, _) = spectrum.truncate(TruncationPolicy::MaxFrequency(7));
+    assert_eq!(truncated.coefficients[0].freq, 1);
+    assert_eq!(truncated.coefficients[1].freq, -2);
+
+    assert_eq!(
+        "energy:99.5".parse::<TruncationPolicy>().unwrap(),
+        TruncationPolicy::Energy(99.5)
+    );
+    assert_eq!(
+        "top:8".parse::<TruncationPolicy>().unwrap(),
+        TruncationPolicy::TopK(8)
+    );
+    for invalid in ["energy:120", "top:-1", "threshold:-1", "freq", "foo:1"] {
+        assert!(invalid.parse::<TruncationPolicy>().is_err(), "{}", invalid);
+    }
+}
+
+/// どの正規化でも半径・再構成・減らし方の結果が同じになり、`1/√N`ではエネルギーが保たれることを確かめる。
+#[test]
+fn test_normalization() {
+    const N: usize = 64;
+    let terms = [(100.0, 1), (20.0, -2), (5.0, 3)];
+    let points = (0..N)
+        .map(|idx| {
+            let t = idx as f64 / N as f64;
+            terms
+                .iter()
+                .map(|&(r, f)| 30.0 + r * Complex::cis(TAU * f as f64 * t))
+                .sum::<Complex<f64>>()
+        })
+        .collect::<Vec<_>>();
+    let normalizations = [
+        FftNormalization::None,
+        FftNormalization::ByLength,
+        FftNormalization::Unitary,
+    ];
+    for normalization in normalizations {
+        let spectrum = Spectrum::new(&points, normalization);
+        assert_eq!(spectrum.normalization, normalization);
+        // 半径と中心のずれ（周波数0）はどの正規化でも同じに読める
+        for &(r, f) in terms.iter() {
+            let radius = spectrum.amplitude(spectrum.coefficient(f)).norm();
+            assert!((radius - r).abs() < 1e-9, "{:?}", normalization);
+        }
+        let offset = spectrum.amplitude(spectrum.coefficient(0));
+        assert!((offset - Complex::new(90.0, 0.0)).norm() < 1e-9);
+
+        for (a, b) in spectrum.reconstruct(N).iter().zip(&points) {
+            assert!((a - b).norm() < 1e-9, "{:?}", normalization);
+        }
+        let t = 0.123;
+        let expected = Spectrum::new(&points, FftNormalization::None).evaluate(t);
+        assert!((spectrum.evaluate(t) - expected).norm() < 1e-9);
+
+        let (truncated, _) = spectrum.truncate(TruncationPolicy::MagnitudeThreshold(10.0));
+        assert_eq!(truncated.coefficients.len(), 3);
+        assert_eq!(truncated.normalization, normalization);
+    }
+
+    // パーセバルの等式
+    let energy = points.iter().map(|p| p.norm_sqr()).sum::<f64>();
+    let unitary = Spectrum::new(&points, FftNormalization::Unitary);
+    assert!((unitary.energy() - energy).abs() < 1e-6 * energy);
+    assert_eq!(
+        "1/sqrt(n)".parse::<FftNormalization>().unwrap(),
+        FftNormalization::Unitary
+    );
+    assert!("sqrt".parse::<FftNormalization>().is_err());
+}
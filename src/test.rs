@@ -1,14 +1,43 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    fs::{create_dir_all, remove_dir_all},
+    path::{Path, PathBuf},
+};
 
 use maplit::hashmap;
 
+/// テスト用の一時ディレクトリ。プロセスIDとテスト名から一意に決め、破棄するときに中身ごと削除する。
+pub(crate) struct TempDir(PathBuf);
+
+impl TempDir {
+    pub(crate) fn new(test_name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("epicycle_{}_{}", std::process::id(), test_name));
+        let _ = remove_dir_all(&path);
+        create_dir_all(&path).unwrap();
+        Self(path)
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.0);
+    }
+}
+
 #[test]
 fn test_read_muni_data() {
     use crate::{
-        io::read_municipalities_boundary_data, municipalities::utils::geo_feature_props_to_array,
+        io::read_municipalities_boundary_data,
+        municipalities::{dataset::Dataset, utils::geo_feature_props_to_array},
     };
 
-    let json_obj = read_municipalities_boundary_data("兵庫県").unwrap();
+    let dataset = Dataset::from_env().unwrap();
+    let json_obj = read_municipalities_boundary_data(&dataset, "兵庫県").unwrap();
     // for feat in json_obj.features.iter() {
     //     println!("{:?}", feat.geometry.coordinates[0].len())
     // }
@@ -16,11 +45,30 @@ fn test_read_muni_data() {
     println!("{:?}", array);
 }
 
+/// データディレクトリの境界データの全ての自治体について、組み込みの読みの表
+/// （とデータディレクトリの読みの表）から読みが引けることを確かめる。
+/// 境界データが必要なので、`cargo test -- --ignored`で明示的に実行する。
+#[test]
+#[ignore = "requires the boundary data in the data directory"]
+fn test_every_municipality_has_reading() {
+    use crate::municipalities::{dataset::Dataset, gazetteer::Gazetteer, readings::ReadingTable};
+
+    let dataset = Dataset::from_env().unwrap();
+    let mut gazetteer = Gazetteer::load_all(&dataset).unwrap();
+    let readings = ReadingTable::for_dataset(&dataset).unwrap();
+    let missing = gazetteer
+        .set_readings(&readings)
+        .iter()
+        .map(|entry| format!("{} {}", entry.code, entry.name))
+        .collect::<Vec<_>>();
+    assert!(missing.is_empty(), "no reading for {:?}", missing);
+}
+
 #[test]
 /// 国土数値情報のGISデータの自治体境界のプロパティオブジェクトから自治体名が生成できることを検証する。
 /// `raw_test_data`にできる限り書きやすい形で記述し、実際に用いられる`String`型に変換してからテストする。
 fn test_get_muni_name() {
-    use crate::municipalities::utils::{geo_feature_props_to_array, props_array_to_name};
+    use crate::municipalities::utils::geo_feature_props_to_name;
     // ここにテストしたいpropertiesを書く
     let raw_test_data: Vec<(HashMap<&str, Option<&str>>, &str)> = vec![
         (
@@ -56,18 +104,23 @@ fn test_get_muni_name() {
         .collect::<Vec<(_, String)>>();
 
     for (props, answer) in test_data.iter() {
-        let array = geo_feature_props_to_array(props);
-        assert!(array.is_ok());
-        let name = props_array_to_name(&array.unwrap());
-        assert_eq!(&name, answer);
+        assert_eq!(&geo_feature_props_to_name(props), answer);
     }
 }
 
 #[test]
 fn test_convert_to_shape() {
     use crate::municipalities::serde_models::{FeatureGeometry, GeoFeature};
-    use crate::municipalities::utils::convert_to_shape;
+    use crate::projection::Projection;
+    use crate::shapes::{merge_features, ShapeOptions};
 
+    let convert_to_shape = |feature: &GeoFeature, num: usize| {
+        let options = ShapeOptions {
+            projection: Projection::LonLat,
+            ..ShapeOptions::new(num)
+        };
+        merge_features("", &[feature], &options).map(|shape| shape.outline())
+    };
     let coordinates = vec![
         vec![0.0, 0.0],
         vec![0.5, 0.0],
@@ -80,12 +133,11 @@ fn test_convert_to_shape() {
     let feature = GeoFeature {
         _type: "".to_string(),
         properties: HashMap::<String, Option<String>>::new(),
-        geometry: FeatureGeometry {
-            _type: "".to_string(),
+        geometry: FeatureGeometry::Polygon {
             coordinates: vec![coordinates],
         },
     };
-    let shape = convert_to_shape(&feature, 4);
+    let shape = convert_to_shape(&feature, 4).unwrap();
     println!("truncation: {:?}", shape);
 
     let coordinates = vec![
@@ -97,11 +149,790 @@ fn test_convert_to_shape() {
     let feature = GeoFeature {
         _type: "".to_string(),
         properties: HashMap::<String, Option<String>>::new(),
-        geometry: FeatureGeometry {
-            _type: "".to_string(),
+        geometry: FeatureGeometry::Polygon {
             coordinates: vec![coordinates],
         },
     };
-    let shape = convert_to_shape(&feature, 11);
+    let shape = convert_to_shape(&feature, 11).unwrap();
     println!("insertion: {:?}", shape);
 }
+
+#[test]
+/// 弧長で等間隔にリサンプリングした点列が元の頂点の密度によらないことを検証する。
+/// `test_convert_to_shape`と同じ三角形（辺の中点を頂点に含むものと含まないもの）と正方形を使う。
+fn test_arc_length_resampling() {
+    use crate::error::Error;
+    use crate::municipalities::serde_models::{FeatureGeometry, GeoFeature};
+    use crate::municipalities::utils::{extract_rings, Resampling, RingPolicy};
+    use rustfft::num_complex::Complex;
+
+    let to_shape = |coordinates: Vec<Vec<f64>>, num: usize, resampling: Resampling| {
+        let feature = GeoFeature {
+            _type: "".to_string(),
+            properties: HashMap::<String, Option<String>>::new(),
+            geometry: FeatureGeometry::Polygon {
+                coordinates: vec![coordinates],
+            },
+        };
+        let rings = extract_rings(&feature, RingPolicy::default()).unwrap();
+        let points = rings.exteriors[0]
+            .iter()
+            .map(|p| Complex::new(p[0], p[1]))
+            .collect::<Vec<_>>();
+        resampling.resample(&points, num).unwrap()
+    };
+    let dense_triangle = vec![
+        vec![0.0, 0.0],
+        vec![0.5, 0.0],
+        vec![1.0, 0.0],
+        vec![0.5, 0.5],
+        vec![0.0, 1.0],
+        vec![0.0, 0.5],
+        vec![0.0, 0.0],
+    ];
+    let sparse_triangle = vec![
+        vec![0.0, 0.0],
+        vec![1.0, 0.0],
+        vec![0.0, 1.0],
+        vec![0.0, 0.0],
+    ];
+
+    for num in [4, 11, 64] {
+        let dense = to_shape(dense_triangle.clone(), num, Resampling::ArcLength);
+        let sparse = to_shape(sparse_triangle.clone(), num, Resampling::ArcLength);
+        assert_eq!(dense.len(), num);
+        assert_eq!(dense.first(), dense.last());
+        for (a, b) in dense.iter().zip(&sparse) {
+            assert!((a - b).norm() < 1e-12);
+        }
+        // 隣り合う点の間の弧長は全て周長の1/(num - 1)で、直線上では弦の長さと一致する
+        let spacing = (2.0 + 2.0f64.sqrt()) / (num - 1) as f64;
+        for pair in dense.windows(2) {
+            assert!((pair[1] - pair[0]).norm() <= spacing + 1e-12);
+        }
+    }
+    // 添字による間引きでは頂点の密度によって結果が変わる
+    assert_ne!(
+        to_shape(dense_triangle, 5, Resampling::Index),
+        to_shape(sparse_triangle, 5, Resampling::Index)
+    );
+
+    // 正方形を9点にすると角と辺の中点に置かれる
+    let square = vec![
+        vec![0.0, 0.0],
+        vec![1.0, 0.0],
+        vec![1.0, 1.0],
+        vec![0.0, 1.0],
+        vec![0.0, 0.0],
+    ];
+    let expected = [
+        (0.0, 0.0),
+        (0.5, 0.0),
+        (1.0, 0.0),
+        (1.0, 0.5),
+        (1.0, 1.0),
+        (0.5, 1.0),
+        (0.0, 1.0),
+        (0.0, 0.5),
+        (0.0, 0.0),
+    ];
+    let shape = to_shape(square, 9, Resampling::ArcLength);
+    for (p, (re, im)) in shape.iter().zip(expected) {
+        assert!((p - Complex::new(re, im)).norm() < 1e-12);
+    }
+
+    // 点の数が2未満の場合や、一点に縮退した環はエラーとする
+    let points = shape.clone();
+    for resampling in [Resampling::Index, Resampling::ArcLength, Resampling::Exact] {
+        for num in [0, 1] {
+            assert!(matches!(
+                resampling.resample(&points, num),
+                Err(Error::InvalidSampleCount(n)) if n == num
+            ));
+        }
+        for degenerate in [vec![], vec![Complex::new(1.0, 1.0); 3]] {
+            assert!(matches!(
+                resampling.resample(&degenerate, 16),
+                Err(Error::DegenerateRing { .. })
+            ));
+        }
+        assert_eq!(resampling.resample(&points, 2).unwrap().len(), 2);
+    }
+}
+
+#[test]
+/// MultiPolygonと穴を含むFeatureが読み込め、穴と飛び地の扱いを指定して境界形状を取り出せることを検証する。
+fn test_multi_polygon_rings() {
+    use crate::geometry::{perimeter, signed_area};
+    use crate::municipalities::serde_models::GeoFeature;
+    use crate::municipalities::utils::{extract_rings, PartHandling, PartKind, RingPolicy};
+    use crate::projection::Projection;
+    use crate::shapes::{merge_features, MergeStrategy, ShapeOptions};
+
+    // 穴を一つ持つ大きな正方形と、小さな島
+    let json = r#"{
+        "type": "Feature",
+        "properties": { "N03_001": "長崎県", "N03_004": "五島市" },
+        "geometry": {
+            "type": "MultiPolygon",
+            "coordinates": [
+                [ [ [2.0, 2.0], [2.5, 2.0], [2.5, 2.5], [2.0, 2.0] ] ],
+                [
+                    [ [0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0], [0.0, 0.0] ],
+                    [ [0.2, 0.2], [0.2, 0.4], [0.4, 0.4], [0.4, 0.2], [0.2, 0.2] ]
+                ]
+            ]
+        }
+    }"#;
+    let feature = serde_json::from_str::<GeoFeature>(json).unwrap();
+    assert_eq!(feature.geometry.polygons().len(), 2);
+
+    // デフォルトでは頂点数の多いポリゴンの外周のみ
+    let rings = extract_rings(&feature, RingPolicy::default()).unwrap();
+    assert_eq!(rings.exteriors.len(), 1);
+    assert!(rings.holes.is_empty());
+    assert_eq!(rings.exteriors[0][1], vec![1.0, 0.0]);
+    assert!(rings.reported.is_empty());
+
+    let policy = RingPolicy {
+        holes: PartHandling::Keep,
+        components: PartHandling::Keep,
+    };
+    let rings = extract_rings(&feature, policy).unwrap();
+    assert_eq!(rings.exteriors.len(), 2);
+    assert_eq!(rings.holes.len(), 1);
+
+    let policy = RingPolicy {
+        holes: PartHandling::Report,
+        components: PartHandling::Report,
+    };
+    let rings = extract_rings(&feature, policy).unwrap();
+    assert_eq!(rings.exteriors.len(), 1);
+    assert!(rings.holes.is_empty());
+    let kinds = rings.reported.iter().map(|r| r.kind).collect::<Vec<_>>();
+    assert_eq!(kinds, vec![PartKind::Hole, PartKind::Component]);
+    assert_eq!(rings.reported[1].polygon_index, 0);
+
+    // 境界形状をまとめる際も同じ扱いになり、除いた部分は形状とともに報告される
+    let options =
+        |strategy: MergeStrategy, holes: PartHandling, components: PartHandling| ShapeOptions {
+            strategy,
+            projection: Projection::LonLat,
+            rings: RingPolicy { holes, components },
+            ..ShapeOptions::new(32)
+        };
+    let shape = merge_features(
+        "長崎県五島市",
+        &[&feature],
+        &options(
+            MergeStrategy::MultiContour,
+            PartHandling::Report,
+            PartHandling::Report,
+        ),
+    )
+    .unwrap();
+    assert_eq!(shape.contours.len(), 1);
+    assert_eq!(shape.reported.len(), 2);
+
+    // 含めた環はつなげず、それぞれ閉じた輪郭としてリサンプリングする
+    let shape = merge_features(
+        "長崎県五島市",
+        &[&feature],
+        &options(
+            MergeStrategy::MultiContour,
+            PartHandling::Keep,
+            PartHandling::Keep,
+        ),
+    )
+    .unwrap();
+    assert_eq!(shape.contours.len(), 3);
+    assert!(shape.reported.is_empty());
+    for contour in shape.contours.iter() {
+        assert!(contour.len() >= 4);
+        assert_eq!(contour.first(), contour.last());
+    }
+
+    // 一つの輪郭にまとめる場合、穴は外周と橋でつながり、穴の分だけ面積が小さく周長が長くなる。
+    // 形状は正規化されるので、大きさによらない面積と周長の二乗の比で比べる
+    let area = |options: &ShapeOptions| {
+        let shape = merge_features("長崎県五島市", &[&feature], options).unwrap();
+        assert_eq!(shape.contours.len(), 1);
+        signed_area(&shape.contours[0]) / perimeter(&shape.contours[0]).powi(2)
+    };
+    let without_hole = area(&options(
+        MergeStrategy::LargestArea,
+        PartHandling::Drop,
+        PartHandling::Drop,
+    ));
+    let with_hole = area(&options(
+        MergeStrategy::LargestArea,
+        PartHandling::Keep,
+        PartHandling::Drop,
+    ));
+    assert!(
+        (without_hole - 1.0 / 16.0).abs() < 0.005,
+        "{}",
+        without_hole
+    );
+    assert!(with_hole < 0.05, "{}", with_hole);
+
+    // ポリゴンを含まない場合はエラー
+    let json = r#"{
+        "type": "Feature",
+        "properties": {},
+        "geometry": { "type": "Point", "coordinates": [135.0, 35.0] }
+    }"#;
+    let feature = serde_json::from_str::<GeoFeature>(json).unwrap();
+    assert!(merge_features("", &[&feature], &ShapeOptions::new(16)).is_err());
+    assert_eq!(
+        "report".parse::<PartHandling>().unwrap(),
+        PartHandling::Report
+    );
+    assert!("hole".parse::<PartHandling>().is_err());
+}
+
+#[test]
+/// 島を持つ自治体について、飛び地のまとめ方ごとに期待される輪郭が得られることを検証する。
+fn test_merge_strategies() {
+    use crate::geometry::signed_area;
+    use crate::municipalities::serde_models::{FeatureGeometry, GeoFeature};
+    use crate::projection::Projection;
+    use crate::shapes::{merge_features, MergeStrategy, ShapeOptions};
+    use crate::similarity::shape_distance;
+
+    let square = |x: f64, y: f64, size: f64| {
+        vec![
+            vec![x, y],
+            vec![x + size, y],
+            vec![x + size, y + size],
+            vec![x, y + size],
+            vec![x, y],
+        ]
+    };
+    // 本土の頂点数は少ないが面積は大きい。島は頂点数が多いが小さい三角形
+    let mut island = vec![
+        vec![3.0, 0.0],
+        vec![3.5, 0.0],
+        vec![3.0, 0.5],
+        vec![3.0, 0.0],
+    ];
+    island.splice(
+        1..1,
+        (1..20).map(|i| vec![3.0 + 0.5 * i as f64 / 20.0, 0.0]),
+    );
+    let feature = |coordinates| GeoFeature {
+        _type: "Feature".to_string(),
+        properties: HashMap::<String, Option<String>>::new(),
+        geometry: FeatureGeometry::Polygon {
+            coordinates: vec![coordinates],
+        },
+    };
+    let mainland = feature(square(0.0, 0.0, 2.0));
+    let island = feature(island);
+    let features = vec![&mainland, &island];
+
+    let shape_with = |strategy| {
+        let options = ShapeOptions {
+            strategy,
+            projection: Projection::LonLat,
+            ..ShapeOptions::new(64)
+        };
+        merge_features("長崎県五島市", &features, &options).unwrap()
+    };
+
+    let largest = shape_with(MergeStrategy::LargestArea);
+    assert_eq!(largest.strategy, MergeStrategy::LargestArea);
+    assert_eq!(largest.contours.len(), 1);
+    assert_eq!(largest.contours[0].len(), 64);
+    // 本土のみから作った形状と一致し、島のみから作った形状とは異なる
+    let options = ShapeOptions {
+        projection: Projection::LonLat,
+        ..ShapeOptions::new(64)
+    };
+    let mainland_only = merge_features("", &[&mainland], &options).unwrap();
+    let island_only = merge_features("", &[&island], &options).unwrap();
+    assert!(shape_distance(&largest.outline(), &mainland_only.outline()) < 1e-10);
+    assert!(shape_distance(&largest.outline(), &island_only.outline()) > 0.1);
+
+    let union = shape_with(MergeStrategy::Union);
+    assert_eq!(union.contours.len(), 1);
+    assert_eq!(union.contours[0].len(), 64);
+
+    let hull = shape_with(MergeStrategy::ConvexHull);
+    assert_eq!(hull.contours.len(), 1);
+    assert!(signed_area(&hull.contours[0]) > 0.0);
+
+    let multi = shape_with(MergeStrategy::MultiContour);
+    assert_eq!(multi.strategy, MergeStrategy::MultiContour);
+    assert_eq!(multi.contours.len(), 2);
+    assert_eq!(
+        multi.outline().len(),
+        multi.contours.iter().map(|c| c.len()).sum::<usize>()
+    );
+}
+
+#[test]
+/// 政令指定都市の区・郡・振興局をまとめた区域名と、区を合併した形状を検証する。
+fn test_area_dissolve() {
+    use rustfft::num_complex::Complex;
+
+    use crate::municipalities::serde_models::{FeatureGeometry, GeoFeature};
+    use crate::municipalities::utils::{geo_feature_props_to_array, AreaLevel};
+    use crate::projection::Projection;
+    use crate::shapes::{dissolve_features, merge_features, MergeStrategy, ShapeOptions};
+
+    let array = |props: HashMap<&str, Option<&str>>| {
+        let props = props
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.map(|v| v.to_string())))
+            .collect();
+        geo_feature_props_to_array(&props)
+    };
+    let ward = array(hashmap![
+        "N03_001" => Some("兵庫県"), "N03_002" => None, "N03_003" => Some("神戸市"), "N03_004" => Some("神戸市東灘区"), "N03_007" => Some("28101")
+    ]);
+    let town = array(hashmap![
+        "N03_001" => Some("京都府"), "N03_002" => None, "N03_003" => Some("与謝郡"), "N03_004" => Some("伊根町"), "N03_007" => Some("26463")
+    ]);
+    let hokkaido = array(hashmap![
+        "N03_001" => Some("北海道"), "N03_002" => Some("石狩振興局"), "N03_003" => Some("札幌市"), "N03_004" => Some("札幌市中央区"), "N03_007" => Some("01101")
+    ]);
+    assert_eq!(
+        AreaLevel::DesignatedCity.area_name(&ward).as_deref(),
+        Some("兵庫県神戸市")
+    );
+    assert_eq!(AreaLevel::County.area_name(&ward), None);
+    assert_eq!(
+        AreaLevel::County.area_name(&town).as_deref(),
+        Some("京都府与謝郡")
+    );
+    assert_eq!(AreaLevel::DesignatedCity.area_name(&town), None);
+    assert_eq!(
+        AreaLevel::Subprefecture.area_name(&hokkaido).as_deref(),
+        Some("北海道石狩振興局")
+    );
+    assert_eq!(
+        AreaLevel::DesignatedCity.area_name(&hokkaido).as_deref(),
+        Some("北海道札幌市")
+    );
+
+    // 辺を共有する二つの区は一つの長方形になる
+    let feature = |x: f64| GeoFeature {
+        _type: "Feature".to_string(),
+        properties: HashMap::<String, Option<String>>::new(),
+        geometry: FeatureGeometry::Polygon {
+            coordinates: vec![vec![
+                vec![x, 0.0],
+                vec![x + 1.0, 0.0],
+                vec![x + 1.0, 1.0],
+                vec![x, 1.0],
+                vec![x, 0.0],
+            ]],
+        },
+    };
+    let (west, east) = (feature(0.0), feature(1.0));
+    let options = ShapeOptions {
+        projection: Projection::LonLat,
+        ..ShapeOptions::new(64)
+    };
+    let city = dissolve_features("兵庫県神戸市", &[&west, &east], &options).unwrap();
+    assert_eq!(city.contours.len(), 1);
+    assert_eq!(city.code, None);
+    let width = |points: &[Complex<f64>]| {
+        let re = points.iter().map(|p| p.re);
+        re.clone().fold(f64::MIN, f64::max) - re.fold(f64::MAX, f64::min)
+    };
+    let height = |points: &[Complex<f64>]| {
+        let im = points.iter().map(|p| p.im);
+        im.clone().fold(f64::MIN, f64::max) - im.fold(f64::MAX, f64::min)
+    };
+    assert!((width(&city.contours[0]) / height(&city.contours[0]) - 2.0).abs() < 1e-9);
+    // 合併しなければ、面積最大の部分として片方の区だけが使われる
+    let ward_only = merge_features("兵庫県神戸市", &[&west, &east], &options).unwrap();
+    assert!((width(&ward_only.contours[0]) / height(&ward_only.contours[0]) - 1.0).abs() < 1e-9);
+
+    let options = ShapeOptions {
+        strategy: MergeStrategy::MultiContour,
+        ..options
+    };
+    let multi = dissolve_features("兵庫県神戸市", &[&west, &east], &options).unwrap();
+    assert_eq!(multi.contours.len(), 1);
+    assert_eq!(
+        "city".parse::<AreaLevel>().unwrap(),
+        AreaLevel::DesignatedCity
+    );
+}
+
+#[test]
+/// 行政区域コードと自治体名の対応表を検証する。
+fn test_gazetteer() {
+    use crate::municipalities::gazetteer::Gazetteer;
+    use crate::municipalities::serde_models::{FeatureGeometry, GeoFeature};
+    use crate::municipalities::utils::{is_muni_code, prefecture_name_from_code};
+
+    let feature = |props: HashMap<&str, Option<&str>>| GeoFeature {
+        _type: "Feature".to_string(),
+        properties: props
+            .into_iter()
+            .map(|(k, v)| (k.to_owned(), v.map(|v| v.to_owned())))
+            .collect(),
+        geometry: FeatureGeometry::Polygon {
+            coordinates: vec![],
+        },
+    };
+    let features = vec![
+        feature(hashmap![
+            "N03_001" => Some("兵庫県"), "N03_002" => None, "N03_003" => None, "N03_004" => Some("姫路市"), "N03_007" => Some("28201")
+        ]),
+        // 同じ自治体の飛び地
+        feature(hashmap![
+            "N03_001" => Some("兵庫県"), "N03_002" => None, "N03_003" => None, "N03_004" => Some("姫路市"), "N03_007" => Some("28201")
+        ]),
+        feature(hashmap![
+            "N03_001" => Some("兵庫県"), "N03_002" => None, "N03_003" => Some("神戸市"), "N03_004" => Some("神戸市東灘区"), "N03_007" => Some("28101")
+        ]),
+        // 所属未定地はコードを持たない
+        feature(hashmap![
+            "N03_001" => Some("兵庫県"), "N03_002" => None, "N03_003" => None, "N03_004" => Some("所属未定地"), "N03_007" => None
+        ]),
+    ];
+    let mut gazetteer = Gazetteer::default();
+    gazetteer.add_features("兵庫県", &features).unwrap();
+
+    let entry = gazetteer.find_by_code("28101").unwrap();
+    assert_eq!(entry.name, "兵庫県神戸市東灘区");
+    assert_eq!(entry.prefecture, "兵庫県");
+    assert_eq!(
+        gazetteer.find_by_name("兵庫県姫路市").unwrap().code,
+        "28201"
+    );
+    assert!(gazetteer.find_by_name("兵庫県所属未定地").is_none());
+    assert!(gazetteer.find_by_code("99999").is_none());
+
+    assert!(is_muni_code("28201"));
+    assert!(!is_muni_code("2820"));
+    assert!(!is_muni_code("兵庫県姫路市"));
+    assert_eq!(prefecture_name_from_code("28201").unwrap(), "兵庫県");
+    assert_eq!(prefecture_name_from_code("01100").unwrap(), "北海道");
+    assert!(prefecture_name_from_code("00000").is_err());
+    assert!(prefecture_name_from_code("48000").is_err());
+}
+
+/// 読み込みや形状の取得に失敗した場合に、原因を示すエラーが返ることを確かめる。
+#[test]
+fn test_typed_errors() {
+    use crate::error::Error;
+    use crate::municipalities::dataset::{Dataset, Vintage};
+    use crate::municipalities::serde_models::{FeatureGeometry, GeoFeature};
+    use crate::municipalities::utils::geo_feature_props_to_name;
+    use crate::shapes::{merge_features, municipality_shape, MergeStrategy, ShapeOptions};
+
+    let dataset = Dataset::new(".", "230101".parse::<Vintage>().unwrap());
+    assert!(matches!(
+        dataset.boundary_data_path("架空県"),
+        Err(Error::UnknownPrefecture(name)) if name == "架空県"
+    ));
+    assert!(matches!(
+        municipality_shape(&dataset, "姫路市", 16),
+        Err(Error::PrefectureNotInName(name)) if name == "姫路市"
+    ));
+
+    let props = hashmap![
+        "N03_001".to_string() => Some("兵庫県".to_string()),
+        "N03_004".to_string() => Some("姫路市".to_string()),
+        "NAME".to_string() => Some("姫路市".to_string()),
+    ];
+    // N03以外のキーがあっても名前は作れる
+    assert_eq!(geo_feature_props_to_name(&props), "兵庫県姫路市");
+
+    let feature = GeoFeature {
+        _type: "".to_string(),
+        properties: hashmap![
+            "N03_001".to_string() => Some("兵庫県".to_string()),
+            "N03_004".to_string() => Some("姫路市".to_string()),
+        ],
+        geometry: FeatureGeometry::Point {
+            coordinates: vec![0.0, 0.0],
+        },
+    };
+    let name = geo_feature_props_to_name(&feature.properties);
+    let err = merge_features(&name, &[&feature], &ShapeOptions::new(16)).unwrap_err();
+    assert!(err.to_string().contains("兵庫県姫路市"));
+    assert!("triangle".parse::<MergeStrategy>().is_err());
+}
+
+#[test]
+/// 都道府県全体と地方全体の形状が、全featureを合併したものになることを検証する。
+fn test_prefecture_and_region_shapes() {
+    use std::fs::write;
+
+    use rustfft::num_complex::Complex;
+
+    use crate::municipalities::{
+        data::REGIONS,
+        dataset::{Dataset, Vintage},
+    };
+    use crate::projection::Projection;
+    use crate::search::prefecture_miniatures;
+    use crate::shapes::{municipality_shape_with_options, prefecture_shape, ShapeOptions};
+
+    let root = TempDir::new("prefecture_and_region_shapes");
+    let dataset = Dataset::new(root.path(), "230101".parse::<Vintage>().unwrap());
+    // 近畿の各府県を横に並んだ1x1の正方形とし、それぞれを縦に2つの自治体に分ける
+    let (_, kinki) = REGIONS.iter().find(|(name, _)| *name == "近畿").unwrap();
+    for (idx, prefecture_name) in kinki.iter().enumerate() {
+        let x = idx as f64;
+        let feature = |name: &str, y: f64| {
+            format!(
+                r#"{{ "type": "Feature", "properties": {{ "N03_001": "{}", "N03_002": null, "N03_003": null, "N03_004": "{}", "N03_007": null }}, "geometry": {{ "type": "Polygon", "coordinates": [ [ [{x}, {y}], [{x1}, {y}], [{x1}, {y1}], [{x}, {y1}], [{x}, {y}] ] ] }} }}"#,
+                prefecture_name,
+                name,
+                x = x,
+                x1 = x + 1.0,
+                y = y,
+                y1 = y + 0.5,
+            )
+        };
+        let json = format!(
+            r#"{{ "type": "FeatureCollection", "name": "test", "crs": {{ "type": "name", "properties": {{ "name": "urn:ogc:def:crs:EPSG::6668" }} }}, "features": [ {}, {} ] }}"#,
+            feature("北市", 0.5),
+            feature("南町", 0.0)
+        );
+        write(dataset.boundary_data_path(prefecture_name).unwrap(), json).unwrap();
+    }
+
+    let options = ShapeOptions {
+        projection: Projection::LonLat,
+        ..ShapeOptions::new(64)
+    };
+    let aspect = |points: &[Complex<f64>]| {
+        let (re, im) = (points.iter().map(|p| p.re), points.iter().map(|p| p.im));
+        (re.clone().fold(f64::MIN, f64::max) - re.fold(f64::MAX, f64::min))
+            / (im.clone().fold(f64::MIN, f64::max) - im.fold(f64::MAX, f64::min))
+    };
+    let prefecture = prefecture_shape(&dataset, "兵庫県", &options).unwrap();
+    assert_eq!(prefecture.name, "兵庫県");
+    assert!((aspect(&prefecture.outline()) - 1.0).abs() < 1e-9);
+    let region = municipality_shape_with_options(&dataset, "近畿", &options).unwrap();
+    assert_eq!(region.contours.len(), 1);
+    assert!((aspect(&region.outline()) - kinki.len() as f64).abs() < 1e-9);
+
+    // 自治体はいずれも2:1の長方形なので、正方形の県とは同じだけ離れている
+    let miniatures = prefecture_miniatures(&dataset, "兵庫県", 2, &options).unwrap();
+    assert_eq!(miniatures.len(), 2);
+    assert!((miniatures[0].1 - miniatures[1].1).abs() < 1e-9);
+    assert!(miniatures[0].1 > 0.0);
+}
+
+/// N03以外のスキーマのGeoJSONを、対応付けに従って読み込めることを確かめる。
+#[test]
+fn test_generic_geojson() {
+    use std::fs::write;
+
+    use crate::io::read_feature_collection;
+    use crate::municipalities::mapping::PropertyMapping;
+    use crate::projection::Projection;
+    use crate::shapes::{mapped_feature_shapes, ShapeOptions};
+
+    // `name`や`crs`を持たず、数値や真偽値のプロパティとジオメトリの無いFeatureを含む
+    let square = |x: f64, size: f64| {
+        format!(
+            r#"{{ "type": "Polygon", "coordinates": [ [ [{x}, 0], [{x1}, 0], [{x1}, {size}], [{x}, {size}], [{x}, 0] ] ] }}"#,
+            x = x,
+            x1 = x + size,
+            size = size
+        )
+    };
+    let json = format!(
+        r#"{{ "type": "FeatureCollection", "features": [
+            {{ "type": "Feature", "properties": {{ "country": "A", "lake": "North", "id": 1, "deep": true }}, "geometry": {} }},
+            {{ "type": "Feature", "properties": {{ "country": "A", "lake": "North", "id": 1, "deep": true }}, "geometry": {} }},
+            {{ "type": "Feature", "properties": {{ "country": "B", "lake": "South", "id": 2, "deep": false }}, "geometry": {} }},
+            {{ "type": "Feature", "properties": {{ "country": "B", "lake": null, "id": 3 }}, "geometry": null }}
+        ] }}"#,
+        square(0.0, 1.0),
+        square(5.0, 0.1),
+        square(10.0, 2.0)
+    );
+    let dir = TempDir::new("generic_geojson");
+    let path = dir.path().join("generic.geojson");
+    let filename = path.to_str().unwrap();
+    write(&path, json).unwrap();
+
+    let features = read_feature_collection(filename).unwrap();
+    assert_eq!(features.len(), 3);
+    assert_eq!(features[0].properties["id"].as_deref(), Some("1"));
+    assert_eq!(features[0].properties["deep"].as_deref(), Some("true"));
+
+    let options = ShapeOptions {
+        projection: Projection::LonLat,
+        ..ShapeOptions::new(32)
+    };
+    let mapping = "country+lake:id".parse::<PropertyMapping>().unwrap();
+    let shapes = mapped_feature_shapes(&features, &mapping, &options).unwrap();
+    assert_eq!(shapes.len(), 2);
+    assert_eq!(shapes[0].name, "A North");
+    assert_eq!(shapes[0].code.as_deref(), Some("1"));
+    assert_eq!(shapes[1].name, "B South");
+    assert_eq!(shapes[0].outline().len(), 32);
+
+    // IDが無ければ表示名ごとにまとめる
+    let mapping = "country".parse::<PropertyMapping>().unwrap();
+    let shapes = mapped_feature_shapes(&features, &mapping, &options).unwrap();
+    assert_eq!(shapes.len(), 2);
+    assert_eq!(shapes[1].code, None);
+
+    // N03の対応付けではプロパティが無いので通し番号を名前とする
+    let shapes = mapped_feature_shapes(&features, &PropertyMapping::n03(), &options).unwrap();
+    let names = shapes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["#0", "#1", "#2"]);
+}
+
+/// TopoJSONのファイルを読み込み、GeoJSONと同じように境界形状を取得して比べられることを確かめる。
+#[test]
+fn test_read_topojson() {
+    use std::fs::write;
+
+    use crate::io::read_feature_collection;
+    use crate::municipalities::mapping::PropertyMapping;
+    use crate::projection::Projection;
+    use crate::shapes::{mapped_feature_shapes, ShapeOptions};
+    use crate::similarity::{FourierDescriptor, DESCRIPTOR_HARMONICS};
+
+    // 辺を共有して横に並ぶ同じ大きさの二つの正方形
+    let json = r#"{ "type": "Topology",
+        "transform": { "scale": [0.001, 0.001], "translate": [135.0, 35.0] },
+        "arcs": [ [[100, 0], [0, 100]], [[100, 100], [-100, 0], [0, -100], [100, 0]], [[100, 0], [100, 0], [0, 100], [-100, 0]] ],
+        "objects": { "towns": { "type": "GeometryCollection", "geometries": [
+            { "type": "Polygon", "arcs": [[0, 1]], "properties": { "N03_001": "兵庫県", "N03_004": "西町", "N03_007": "28001" } },
+            { "type": "Polygon", "arcs": [[2, -1]], "properties": { "N03_001": "兵庫県", "N03_004": "東町", "N03_007": "28002" } }
+        ] } } }"#;
+    let dir = TempDir::new("read_topojson");
+    let path = dir.path().join("towns.topojson");
+    write(&path, json).unwrap();
+
+    let features = read_feature_collection(path.to_str().unwrap()).unwrap();
+    let options = ShapeOptions {
+        projection: Projection::LonLat,
+        ..ShapeOptions::new(64)
+    };
+    let shapes = mapped_feature_shapes(&features, &PropertyMapping::n03(), &options).unwrap();
+    let names = shapes.iter().map(|s| s.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names, ["兵庫県西町", "兵庫県東町"]);
+    assert_eq!(shapes[1].code.as_deref(), Some("28002"));
+    let descriptors = shapes
+        .iter()
+        .map(|s| FourierDescriptor::new(&s.outline(), DESCRIPTOR_HARMONICS))
+        .collect::<Vec<_>>();
+    assert!(descriptors[0].distance(&descriptors[1]) < 1e-6);
+}
+
+#[test]
+/// 多角形の解析的なフーリエ係数が頂点の密度によらず、弧長で細かく等間隔にリサンプリングした点列のFFTと一致することを検証する。
+/// `test_arc_length_resampling`と同じ三角形と、角度で標本化した（頂点の間隔が不均一な）`rectangle`を使う。
+fn test_polygon_spectrum() {
+    use crate::fft::FftNormalization;
+    use crate::municipalities::utils::{resample_arc_length, Resampling};
+    use crate::shapes::rectangle;
+    use crate::spectrum::Spectrum;
+    use rustfft::num_complex::Complex;
+
+    const LEN: usize = 64;
+    const FINE: usize = 8192;
+    let to_points = |coordinates: &[(f64, f64)]| {
+        coordinates
+            .iter()
+            .map(|&(re, im)| Complex::new(re, im))
+            .collect::<Vec<_>>()
+    };
+    let dense_triangle = to_points(&[
+        (0.0, 0.0),
+        (0.5, 0.0),
+        (1.0, 0.0),
+        (0.5, 0.5),
+        (0.0, 1.0),
+        (0.0, 0.5),
+        (0.0, 0.0),
+    ]);
+    // 閉じていない点列は最後の頂点から最初の頂点への辺を補う
+    let sparse_triangle = to_points(&[(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)]);
+    let rectangle = rectangle();
+
+    // 弧長で等間隔にFINE点を取ってFFTし、低い周波数の係数だけをLEN点のFFTの並びで取り出す
+    let sampled = |ring: &[Complex<f64>]| {
+        let mut points = resample_arc_length(ring, FINE + 1);
+        points.pop();
+        Spectrum::new(&points, FftNormalization::ByLength)
+    };
+    for ring in [&dense_triangle, &rectangle] {
+        let exact = Spectrum::from_polygon(ring, LEN, FftNormalization::ByLength);
+        let sampled = sampled(ring);
+        assert_eq!(exact.coefficients.len(), LEN);
+        // 折り返しの誤差は形の大きさに比例する
+        let size = ring.iter().map(|p| p.norm()).fold(0.0, f64::max);
+        for c in exact.coefficients.iter() {
+            assert!(
+                (c.value - sampled.coefficient(c.freq)).norm() < 1e-7 * size,
+                "freq {}",
+                c.freq
+            );
+        }
+    }
+
+    // 辺の上の頂点は係数を変えない。周波数0は周上の点の重心
+    let dense = Spectrum::from_polygon(&dense_triangle, LEN, FftNormalization::None);
+    let sparse = Spectrum::from_polygon(&sparse_triangle, LEN, FftNormalization::None);
+    for (a, b) in dense.coefficients.iter().zip(&sparse.coefficients) {
+        assert!((a.value - b.value).norm() < 1e-9);
+    }
+    let perimeter = 2.0 + 2.0f64.sqrt();
+    let centroid =
+        (Complex::new(0.5, 0.0) + Complex::new(0.0, 0.5) + Complex::new(0.5, 0.5) * 2.0f64.sqrt())
+            / perimeter;
+    assert!((dense.amplitude(dense.coefficient(0)) - centroid).norm() < 1e-12);
+
+    // 頂点で再構成すると頂点を通る。正方形の係数は奇数次のみで、周波数1, -3, 5, ...に現れる
+    let square = to_points(&[(1.0, -1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)]);
+    let exact = Spectrum::from_polygon(&square, 4096, FftNormalization::Unitary);
+    for (idx, p) in exact.reconstruct(8).iter().enumerate() {
+        let expected = [
+            Complex::new(1.0, -1.0),
+            Complex::new(1.0, 0.0),
+            Complex::new(1.0, 1.0),
+            Complex::new(0.0, 1.0),
+            Complex::new(-1.0, 1.0),
+            Complex::new(-1.0, 0.0),
+            Complex::new(-1.0, -1.0),
+            Complex::new(0.0, -1.0),
+        ][idx];
+        assert!((p - expected).norm() < 1e-3, "{} {}", idx, p);
+    }
+    // 解析的な係数による点の取り方は、点の数のFFTで同じ係数に戻る
+    let mut points = Resampling::Exact.resample(&square, 9).unwrap();
+    assert_eq!(points.len(), 9);
+    assert_eq!(points.first(), points.last());
+    points.pop();
+    let resampled = Spectrum::new(&points, FftNormalization::ByLength);
+    let exact_8 = Spectrum::from_polygon(&square, 8, FftNormalization::ByLength);
+    for c in exact_8.coefficients.iter() {
+        assert!((resampled.coefficient(c.freq) - c.value).norm() < 1e-12);
+    }
+    for freq in [0, 2, -1, 3, -2, 4] {
+        assert!(exact.coefficient(freq).norm() < 1e-9, "{}", freq);
+    }
+    for freq in [1, -3, 5, -7] {
+        assert!(exact.coefficient(freq).norm() > 1e-3, "{}", freq);
+    }
+
+    // 頂点の間隔が不均一な点列を直接FFTすると、解析的な係数からずれる
+    let uneven = Spectrum::new(
+        &rectangle[..rectangle.len() - 1],
+        FftNormalization::ByLength,
+    );
+    let exact = Spectrum::from_polygon(&rectangle, uneven.len, FftNormalization::ByLength);
+    assert!((uneven.coefficient(1) - exact.coefficient(1)).norm() > 1e-2);
+
+    let empty = Spectrum::from_polygon(&[Complex::new(3.0, 4.0)], 8, FftNormalization::None);
+    assert_eq!(empty.coefficient(0), Complex::new(3.0 * 8.0, 4.0 * 8.0));
+    assert_eq!(empty.coefficient(1), Complex::new(0.0, 0.0));
+}